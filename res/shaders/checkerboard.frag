@@ -4,12 +4,11 @@ in vec4 col;
 in vec3 normal;
 in vec2 uv;
 
+uniform sampler2D u_checker;
+
 out vec4 frag_col;
 
 void main() {
-
-    vec2 check = fract(uv * 30.0);
-    float c = (check.x + check.y) / 2.0;
-    c = c > 0.5 ? 1 : 0;
-    frag_col = vec4(c, c, c, 1.0) * 0.5;
+    // the texture holds 2x2 squares, so this gives 30 squares across like before
+    frag_col = texture(u_checker, uv * 15.0) * 0.5;
 }
@@ -1,8 +1,6 @@
 #version 460
 
-layout (location = 0) in vec3 in_pos;
-layout (location = 1) in vec3 in_normal;
-layout (location = 2) in vec2 in_uv;
+#include "common/vertex_inputs.glsl"
 
 uniform mat4 pvm;
 uniform vec4 tint;
@@ -0,0 +1,8 @@
+#pragma once
+
+layout (std140, binding = 0) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 pv;
+    vec4 position;
+} camera;
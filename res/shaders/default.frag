@@ -4,10 +4,33 @@ in vec4 col;
 in vec3 normal;
 in vec2 uv;
 
-uniform sampler2D u_texture;
+uniform vec4 material_diffuse;
+uniform vec3 material_emissive;
+#ifdef HAS_DIFFUSE_MAP
+uniform sampler2D material_diffuse_map;
+#endif
+#ifdef HAS_EMISSIVE_MAP
+uniform sampler2D material_emissive_map;
+#endif
+#ifdef ALPHA_TEST
+uniform float material_alpha_cutoff;
+#endif
 
 out vec4 frag_col;
 
 void main() {
-    frag_col = texture(u_texture, uv) * col;
+    frag_col = col * material_diffuse;
+#ifdef HAS_DIFFUSE_MAP
+    frag_col *= texture(material_diffuse_map, uv);
+#endif
+#ifdef ALPHA_TEST
+    if (frag_col.a < material_alpha_cutoff) {
+        discard;
+    }
+#endif
+    vec3 emissive = material_emissive;
+#ifdef HAS_EMISSIVE_MAP
+    emissive *= texture(material_emissive_map, uv).rgb;
+#endif
+    frag_col.rgb += emissive;
 }
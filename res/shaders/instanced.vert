@@ -0,0 +1,19 @@
+#version 460
+
+#include "common/vertex_inputs.glsl"
+#include "common/camera.glsl"
+
+layout (location = 8) in mat4 in_model;
+layout (location = 12) in vec4 in_tint;
+layout (location = 13) in vec4 in_custom;
+
+out vec4 col;
+out vec3 normal;
+out vec2 uv;
+
+void main() {
+    gl_Position = camera.pv * in_model * vec4(in_pos.xyz, 1.0);
+    col = vec4(1.0) * in_tint;
+    normal = mat3(in_model) * in_normal;
+    uv = in_uv;
+}
@@ -0,0 +1,9 @@
+#version 460
+
+in vec4 col;
+
+out vec4 frag_col;
+
+void main() {
+    frag_col = col;
+}
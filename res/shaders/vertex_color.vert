@@ -0,0 +1,13 @@
+#version 460
+
+layout (location = 0) in vec3 in_pos;
+layout (location = 4) in vec4 in_color;
+
+uniform mat4 pvm;
+
+out vec4 col;
+
+void main() {
+    gl_Position = pvm * vec4(in_pos, 1.0);
+    col = in_color;
+}
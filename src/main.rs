@@ -2,39 +2,49 @@ mod renderer;
 
 use std::cell::RefCell;
 use std::f32::consts::{PI, TAU};
-use crate::renderer::mesh::Mesh;
+use crate::renderer::instancing::{InstanceData, InstancedModel};
+use crate::renderer::mesh::{LoadedModel, Mesh, Topology};
+use crate::renderer::mesh_data::MeshData;
 use crate::renderer::shader::Shader;
+use crate::renderer::streaming_mesh::StreamingMesh;
+use crate::renderer::shader_variants::ShaderVariants;
+use crate::renderer::shader_watcher::{self, ReloadEvent};
+use crate::renderer::spirv;
+use crate::renderer::material::Material;
 use crate::renderer::model::{Model, Transform};
-use glm::{cos, pow, sin, Vec3, Vec4};
+use glm::{cos, sin, Vec3, Vec4};
 use num_traits::identities::One;
 use sdl3::event::{Event, WindowEvent};
 use sdl3::keyboard::Keycode;
 use std::ffi::{c_void, CStr};
-use std::path::{PathBuf};
+use std::path::Path;
 use std::ptr::null;
 use std::rc::Rc;
 use std::time::Duration;
-use gl::types::{GLenum, GLint, GLuint};
-use image::{EncodableLayout, ImageReader};
+use image::{DynamicImage, Rgba, RgbaImage};
 use num_traits::Zero;
-use crate::renderer::camera::Camera;
-use crate::renderer::texture::Texture;
+use crate::renderer::buffer::{BufferTarget, BufferUsage, GpuBuffer};
+use crate::renderer::camera::{Camera, CAMERA_BINDING};
+use crate::renderer::gl_caps;
+use crate::renderer::sampler::{Sampler, SamplerDesc};
+use crate::renderer::scene::Scene;
+use crate::renderer::framebuffer::Framebuffer;
+use crate::renderer::compute::{self, Barrier};
+use crate::renderer::texture::{Texture, TextureUnit};
+use crate::renderer::texture_format::TextureFormat;
+use crate::renderer::vertex::ColorVertex;
+use crate::renderer::vertex_layout::Normalized;
 
 const DEG_TO_RAD: f32 = TAU / 360.0;
 const RAD_TO_DEG: f32 = 360.0 / TAU;
+const WAVE_POINTS: usize = 128;
 
-fn main() {
-    
-    // let cube = Mesh::from_model(&PathBuf::from("res/models/cube.obj"));
-    //
-    // return;
-    
-    
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut width = 1920;
     let mut height = 1080;
     
-    let sdl_context = sdl3::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
+    let sdl_context = sdl3::init()?;
+    let video_subsystem = sdl_context.video()?;
     
     let gl_attr = video_subsystem.gl_attr();
     gl_attr.set_context_profile(sdl3::video::GLProfile::Core);
@@ -42,18 +52,18 @@ fn main() {
     gl_attr.set_double_buffer(true);
     gl_attr.set_multisample_buffers(1);
     gl_attr.set_multisample_samples(4);
+    gl_attr.set_framebuffer_srgb_compatible(true);
     
     let window = video_subsystem.window("Hellowo Katharina", width, height)
         .opengl()
         .position_centered()
         .resizable()
-        .build()
-        .unwrap();
+        .build()?;
         
-    let _gl_context = window.gl_create_context().unwrap();
-    window.gl_make_current(&_gl_context).unwrap();
+    let _gl_context = window.gl_create_context()?;
+    window.gl_make_current(&_gl_context)?;
     
-    let display = window.get_display().unwrap();
+    let display = window.get_display()?;
     let bounds = display.get_bounds();
     println!("bounds: {bounds:?}");
     
@@ -61,7 +71,11 @@ fn main() {
     let mut aspect_ratio = width as f32 / height as f32;
     
     gl::load_with(|s| {
-        video_subsystem.gl_get_proc_address(s).unwrap() as *const c_void
+        video_subsystem.gl_get_proc_address(s).map_or(null(), |f| f as *const c_void)
+    });
+    // not part of the `gl` bindings, may be missing
+    spirv::load_with(|s| {
+        video_subsystem.gl_get_proc_address(s).map_or(null(), |f| f as *const c_void)
     });
     
     unsafe {
@@ -69,15 +83,32 @@ fn main() {
         println!("OpenGL version: {}", version.to_string_lossy());
     }
     
-    let mut default_shader = Rc::new(RefCell::new(
-        Shader::new(&PathBuf::from("res/shaders/default.vert"), &PathBuf::from("res/shaders/default.frag")).unwrap()
-    ));
+    let caps = gl_caps::init();
+    println!("Direct state access: {}", caps.dsa);
     
-    let mut checkerboard_shader = Rc::new(RefCell::new(
-        Shader::new(&PathBuf::from("res/shaders/checkerboard.vert"), &PathBuf::from("res/shaders/checkerboard.frag")).unwrap()
-    ));
+    // shared shaders are recompiled by `shader_watcher` when their files change
+    let default_shader = Shader::new(Path::new("res/shaders/default.vert"), Path::new("res/shaders/default.frag"))?.shared();
+    
+    let checkerboard_shader = Shader::new(Path::new("res/shaders/checkerboard.vert"), Path::new("res/shaders/checkerboard.frag"))?.shared();
+    
+    let instanced_shader = Shader::new(Path::new("res/shaders/instanced.vert"), Path::new("res/shaders/default.frag"))?.shared();
     
-    let texture = Texture::new("res/textures/prettyface.jpg").unwrap();
+    // a missing texture shouldn't stop the demo, magenta makes it easy to spot
+    let face = Rc::new(Texture::new("res/textures/prettyface.jpg").or_else(|e| {
+        eprintln!("Warning: {}", e);
+        Texture::from_color(Vec3::new(1.0, 0.0, 1.0))
+    })?);
+    let face_material = Material {
+        name: String::from("face"),
+        diffuse_map: Some(face.clone()),
+        ..Material::default()
+    };
+    
+    // tiled across the whole floor, mipmaps and anisotropic filtering keep it from shimmering
+    let checker_image = RgbaImage::from_fn(64, 64, |x, y| {
+        if (x / 32 + y / 32) % 2 == 0 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) }
+    });
+    let checker = Rc::new(Texture::from_image_with_sampler(&DynamicImage::ImageRgba8(checker_image), SamplerDesc::default().with_anisotropy(16.0))?);
     
     let quad_mesh = Rc::new(RefCell::new(
         Mesh::quad()
@@ -85,25 +116,143 @@ fn main() {
     let cube_mesh = Rc::new(RefCell::new(
         Mesh::cube()
     ));
+    let sphere_mesh = Rc::new(RefCell::new(
+        MeshData::uv_sphere(0.5, 32, 16).upload()
+    ));
+    let torus_mesh = Rc::new(RefCell::new(
+        MeshData::torus(0.5, 0.15, 32, 16).upload()
+    ));
     
-    let cube1 = Model::with_transform(
+    // default.vert/frag compiled once per combination of material features
+    let mut material_shaders = ShaderVariants::new(Path::new("res/shaders/default.vert"), Path::new("res/shaders/default.frag"));
+    
+    let mut cube1 = Model::with_material(
         cube_mesh.clone(), default_shader.clone(),
-        Transform::new(Vec3::new(0.0, 0.0, 0.0), Vec3::one(), Vec3::zero())
+        Transform::new(Vec3::new(0.0, 0.0, 0.0), Vec3::one(), Vec3::zero()),
+        face_material.clone()
     );
-    let mut quad1 = Model::with_transform(
+    let mut quad1 = Model::with_material(
         quad_mesh.clone(), default_shader.clone(),
-        Transform::new(Vec3::new(1.5, 0.75, 0.0), Vec3::one(), Vec3::zero())
+        Transform::new(Vec3::new(1.5, 0.75, 0.0), Vec3::one(), Vec3::zero()),
+        face_material.clone()
     );
-    let mut cube2 = Model::with_transform(
+    let mut cube2 = Model::with_material(
         cube_mesh.clone(), default_shader.clone(),
-        Transform::new(Vec3::new(-3.0, 0.0, -5.0), Vec3::one(), Vec3::zero())
+        Transform::new(Vec3::new(-3.0, 0.0, -5.0), Vec3::one(), Vec3::zero()),
+        face_material.clone()
+    );
+    for model in [&mut cube1, &mut quad1, &mut cube2] {
+        if let Err(e) = model.use_variant(&mut material_shaders) {
+            eprintln!("Could not compile shader variant for '{}': {}", model.material().name, e);
+        }
+    }
+    let sphere = Model::with_transform(
+        sphere_mesh.clone(), default_shader.clone(),
+        Transform::new(Vec3::new(-1.5, 0.0, -1.5), Vec3::one(), Vec3::zero())
+    );
+    let torus = Model::with_transform(
+        torus_mesh.clone(), default_shader.clone(),
+        Transform::new(Vec3::new(0.0, 0.0, -3.0), Vec3::one(), Vec3::new(PI / 2.0, 0.0, 0.0))
+    );
+    
+    let cube_model = Mesh::from_model_with_materials(Path::new("res/models/cube.obj"))
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            LoadedModel { submeshes: Vec::new(), warnings: Vec::new() }
+        });
+    for warning in &cube_model.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    let obj_cube: Vec<Model> = cube_model.submeshes
+        .into_iter()
+        .map(|submesh| {
+            let mut model = Model::from_submesh(
+                submesh, default_shader.clone(),
+                Transform::new(Vec3::new(3.0, 0.0, -2.0), Vec3::new(0.5, 0.5, 0.5), Vec3::zero())
+            );
+            if let Err(e) = model.use_variant(&mut material_shaders) {
+                eprintln!("Could not compile shader variant for '{}': {}", model.material().name, e);
+            }
+            model
+        })
+        .collect();
+    
+    // the world axes, red x, green y and blue z
+    let axes_shader = Shader::new(Path::new("res/shaders/vertex_color.vert"), Path::new("res/shaders/vertex_color.frag"))?.shared();
+    let axis = |direction: Vec3, color: [u8; 4]| [
+        ColorVertex { v: Vec3::zero(), color: Normalized(color) },
+        ColorVertex { v: direction, color: Normalized(color) },
+    ];
+    let axes_vertices: Vec<ColorVertex> = [
+        axis(Vec3::new(1.0, 0.0, 0.0), [255, 0, 0, 255]),
+        axis(Vec3::new(0.0, 1.0, 0.0), [0, 255, 0, 255]),
+        axis(Vec3::new(0.0, 0.0, 1.0), [0, 0, 255, 255]),
+    ].concat();
+    let axes = Model::with_transform(
+        Rc::new(RefCell::new(Mesh::from_vertices(&axes_vertices, &[], Topology::Lines, BufferUsage::Static))),
+        axes_shader.clone(),
+        Transform::new(Vec3::new(-1.5, -0.99, 1.0), Vec3::one(), Vec3::zero())
+    );
+    
+    // the spinning cube seen from another angle, rendered off-screen and shown on a screen next to it
+    let monitor = Framebuffer::with_formats(256, 256, &[TextureFormat::Srgb8Alpha8], Some(TextureFormat::Depth24))?;
+    let monitor_pv = glm::ext::perspective(50.0 * DEG_TO_RAD, 1.0, 0.1, 100.0)
+        * glm::ext::look_at(Vec3::new(2.0, 1.5, 2.5), Vec3::zero(), Vec3::new(0.0, 1.0, 0.0));
+    let screen_shader = Shader::new(Path::new("res/shaders/default.vert"), Path::new("res/shaders/screen.frag"))?.shared();
+    let screen = Model::with_transform(
+        quad_mesh.clone(), screen_shader.clone(),
+        Transform::new(Vec3::new(-2.0, 0.5, 0.5), Vec3::one(), Vec3::new(0.0, 0.4, 0.0))
+    );
+    
+    // a plasma texture animated by a compute shader, shown on a second screen
+    const PLASMA_SIZE: u32 = 128;
+    let mut plasma_shader = Shader::compute(Path::new("res/shaders/plasma.comp"))?;
+    let plasma = Texture::empty(PLASMA_SIZE as i32, PLASMA_SIZE as i32)?;
+    // overrides the texture's linear filtering, so the plasma's texels show
+    let pixelated = Sampler::new(SamplerDesc::nearest());
+    let plasma_screen = Model::with_transform(
+        quad_mesh.clone(), screen_shader.clone(),
+        Transform::new(Vec3::new(-3.5, 0.5, 0.0), Vec3::one(), Vec3::new(0.0, 0.8, 0.0))
+    );
+    
+    // rewritten every frame through persistently mapped buffers, drawn with the axes' shader
+    let mut wave = StreamingMesh::<ColorVertex>::new(WAVE_POINTS, 0, Topology::LineStrip)
+        .map_err(|e| eprintln!("Warning: {}", e))
+        .ok();
+    
+    let mut pyramids = Scene::from_gltf(Path::new("res/models/pyramid.gltf"), default_shader.clone())
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            Scene { nodes: Vec::new() }
+        });
+    
+    // a 20x20 forest of cubes in a single draw call
+    let forest = InstancedModel::new(
+        cube_mesh.clone(), instanced_shader.clone(),
+        (0..400).map(|n| {
+            let (x, z) = ((n % 20) as f32, (n / 20) as f32);
+            let transform = Transform::new(Vec3::new(x * 2.0 - 19.0, -0.75, -10.0 - z * 2.0), Vec3::new(0.5, 0.5, 0.5), Vec3::new(0.0, x * z, 0.0));
+            InstanceData::from_transform(&transform)
+                .with_tint(Vec4::new(x / 20.0, 0.5, z / 20.0, 1.0))
+        }).collect()
     );
     
-    let floor = Model::with_transform(
+    let floor = Model::with_material(
         quad_mesh.clone(), checkerboard_shader.clone(),
-        Transform::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(50.0, 50.0, 1.0), Vec3::new(-std::f32::consts::PI / 2.0, 0.0, 0.0))
+        Transform::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(50.0, 50.0, 1.0), Vec3::new(-std::f32::consts::PI / 2.0, 0.0, 0.0)),
+        Material::default().with_texture("u_checker", checker)
     );
     
+    // catch meshes that don't supply what their shader reads
+    for model in [&cube1, &quad1, &cube2, &sphere, &torus, &floor, &axes, &screen, &plasma_screen].into_iter().chain(obj_cube.iter()) {
+        if let Err(e) = model.check_layout() {
+            eprintln!("Warning: {}", e);
+        }
+    }
+    if let Err(e) = forest.check_layout() {
+        eprintln!("Warning: {}", e);
+    }
+    
     // floor.set_tint(Vec4::new(50.0 / 255.0, 50.0 / 255.0, 50.0 / 255.0, 1.0));
     // quad2.set_tint(Vec4::new(0.0, 1.0, 0.0, 1.0));
     // quad3.set_tint(Vec4::new(0.0, 0.0, 1.0, 1.0));
@@ -113,6 +262,8 @@ fn main() {
         gl::Enable(gl::MULTISAMPLE);
         gl::Enable(gl::LINE_SMOOTH);
         gl::Enable(gl::CULL_FACE);
+        // shaders output linear colours, sRGB textures are decoded when sampled
+        gl::Enable(gl::FRAMEBUFFER_SRGB);
         gl::ClearColor(0.1, 0.3, 0.2, 1.0);
     }
     
@@ -122,13 +273,16 @@ fn main() {
         70.0, aspect_ratio, 0.01, 100.0
     );
     
+    let mut camera_buffer = GpuBuffer::from_data(BufferTarget::Uniform, &[camera.uniforms()], BufferUsage::Dynamic);
+    camera_buffer.bind_base(CAMERA_BINDING)?;
+    
     let mut keycodes: Vec<Keycode> = Vec::new();
     
     let mut speed = 4.0;
     let mut rot_speed = 2.0;
     
     let mut i = 0f32;
-    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut event_pump = sdl_context.event_pump()?;
     'running: loop {
         let dt = 1.0/60.0;
         for event in event_pump.poll_iter() {
@@ -144,17 +298,7 @@ fn main() {
                         Keycode::Escape => break 'running,
                         Keycode::R => {
                             println!("Reloading shaders");
-                            default_shader.borrow().unbind();
-                            match default_shader.borrow_mut().reload() {
-                                Ok(_) => { println!("default_shader reloaded!") }
-                                Err(e) => { eprintln!("default_shader compilation failed: {}", e) }
-                            }
-                            
-                            checkerboard_shader.borrow().unbind();
-                            match checkerboard_shader.borrow_mut().reload() {
-                                Ok(_) => { println!("shader_checkerboard reloaded!") }
-                                Err(e) => { eprintln!("shader_checkerboard compilation failed: {}", e) }
-                            }
+                            report_reloads(shader_watcher::reload_all());
                         }
                         _ => {}
                     }
@@ -195,9 +339,17 @@ fn main() {
         let old_rot = cube1.transform().rotation();
         cube1.transform_mut().set_rotation(Vec3::new(old_rot.x + 0.034, old_rot.y + 0.05, old_rot.z - 0.01));
         
+        let old_rot = torus.transform().rotation();
+        torus.transform_mut().set_rotation(Vec3::new(old_rot.x, old_rot.y + 0.01, old_rot.z));
+        
         let old_rot = quad1.transform().rotation();
         quad1.transform_mut().set_rotation(Vec3::new(old_rot.x, old_rot.y + 0.02, old_rot.z));
         
+        if let Some(node) = pyramids.find_mut("small") {
+            let old_rot = node.transform.rotation();
+            node.transform.set_rotation(Vec3::new(old_rot.x, old_rot.y - 0.02, old_rot.z));
+        }
+        
         // let old_pos = floor.transform().pos();
         // floor.transform_mut().set_pos(Vec3::new(old_pos.x, old_pos.y - 0.01, old_pos.z));
         
@@ -266,16 +418,89 @@ fn main() {
         }
         
         
-        texture.bind();
+        // pick up edits to shader files and their includes
+        report_reloads(shader_watcher::poll());
+        
+        camera_buffer.upload(&[camera.uniforms()]);
         
-        floor.render(camera.pv_mat());
-        cube1.render(camera.pv_mat());
-        cube2.render(camera.pv_mat());
-        quad1.render(camera.pv_mat());
+        plasma_shader.bind();
+        plasma_shader.set("u_time", i * 5.0)?;
+        unsafe {
+            gl::BindImageTexture(0, plasma.id(), 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RGBA8);
+        }
+        let [x, y, z] = plasma_shader.work_groups_for([PLASMA_SIZE, PLASMA_SIZE, 1]);
+        plasma_shader.dispatch(x, y, z);
+        // the screen samples what the dispatch wrote
+        compute::memory_barrier(Barrier::TEXTURE_FETCH);
+        
+        monitor.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        cube1.render(monitor_pv)?;
+        monitor.unbind();
+        unsafe {
+            gl::Viewport(0, 0, width as i32, height as i32);
+        }
+        
+        floor.render(camera.pv_mat())?;
+        cube1.render(camera.pv_mat())?;
+        cube2.render(camera.pv_mat())?;
+        quad1.render(camera.pv_mat())?;
+        sphere.render(camera.pv_mat())?;
+        torus.render(camera.pv_mat())?;
+        for model in obj_cube.iter() {
+            model.render(camera.pv_mat())?;
+        }
+        forest.render(camera.pv_mat())?;
+        pyramids.render(camera.pv_mat())?;
+        axes.render(camera.pv_mat())?;
+        {
+            let mut shader = screen_shader.borrow_mut();
+            shader.bind();
+            shader.set("u_screen", TextureUnit::new(monitor.color(0), 0))?;
+        }
+        screen.render(camera.pv_mat())?;
+        {
+            let mut shader = screen_shader.borrow_mut();
+            shader.bind();
+            shader.set("u_screen", TextureUnit::new(&plasma, 0))?;
+        }
+        pixelated.bind(0);
+        plasma_screen.render(camera.pv_mat())?;
+        pixelated.unbind(0);
+        if let Some(wave) = &mut wave {
+            let points: Vec<ColorVertex> = (0..WAVE_POINTS).map(|n| {
+                let x = n as f32 / (WAVE_POINTS - 1) as f32;
+                ColorVertex {
+                    v: Vec3::new(x * 4.0 - 2.0, 2.0 + sin(x * TAU * 2.0 - i * 5.0) * 0.25, 1.0),
+                    color: Normalized([255, (x * 255.0) as u8, 0, 255]),
+                }
+            }).collect();
+            match wave.update(&points, &[]) {
+                Ok(()) => {
+                    let mut shader = axes_shader.borrow_mut();
+                    shader.bind();
+                    shader.set("pvm", camera.pv_mat())?;
+                    wave.render();
+                }
+                Err(e) => eprintln!("Warning: {}", e),
+            }
+        }
         
         window.gl_swap_window();
         
         i += 0.01;
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+fn report_reloads(events: Vec<ReloadEvent>) {
+    for event in events {
+        match event.result {
+            Ok(_) => { println!("{} reloaded!", event.label) }
+            Err(e) => { eprintln!("{} compilation failed, keeping the last good program: {}", event.label, e) }
+        }
+    }
+}
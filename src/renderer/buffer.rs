@@ -1,4 +1,10 @@
-use crate::renderer::vertex::Vertex;
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ptr::null;
+use gl::types::GLsync;
+use crate::renderer::error::{check_gl, RendererError};
+use crate::renderer::gl_caps;
+use crate::renderer::vertex_layout::{AttributeKind, VertexAttribute};
 
 pub trait Buffer {
     fn new() -> Self;
@@ -6,8 +12,194 @@ pub trait Buffer {
     fn unbind(&self);
 }
 
+/// How often the contents of a buffer are expected to change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// Uploaded once, drawn many times.
+    Static,
+    /// Updated now and then, drawn many times.
+    Dynamic,
+    /// Replaced about every frame.
+    Stream,
+}
+
+impl BufferUsage {
+    pub fn gl_usage(&self) -> u32 {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+/// What a `GpuBuffer` is bound as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferTarget {
+    Array,
+    Element,
+    Uniform,
+    ShaderStorage,
+    DrawIndirect,
+    PixelPack,
+    PixelUnpack,
+}
+
+impl BufferTarget {
+    pub fn gl_target(&self) -> u32 {
+        match self {
+            BufferTarget::Array => gl::ARRAY_BUFFER,
+            BufferTarget::Element => gl::ELEMENT_ARRAY_BUFFER,
+            BufferTarget::Uniform => gl::UNIFORM_BUFFER,
+            BufferTarget::ShaderStorage => gl::SHADER_STORAGE_BUFFER,
+            BufferTarget::DrawIndirect => gl::DRAW_INDIRECT_BUFFER,
+            BufferTarget::PixelPack => gl::PIXEL_PACK_BUFFER,
+            BufferTarget::PixelUnpack => gl::PIXEL_UNPACK_BUFFER,
+        }
+    }
+
+    /// Uniform and storage buffers are bound to numbered binding points.
+    pub fn is_indexed(&self) -> bool {
+        matches!(self, BufferTarget::Uniform | BufferTarget::ShaderStorage)
+    }
+}
+
+/// Buffer holding elements of type `T`. For uniform and storage buffers `T`
+/// has to match the std140/std430 layout of the block, e.g. by only using
+/// `Mat4`, `Vec4` and scalars padded to 16 bytes.
+pub struct GpuBuffer<T: Copy> {
+    id: u32,
+    target: BufferTarget,
+    usage: BufferUsage,
+    /// Elements written by the last `upload`.
+    len: usize,
+    /// Allocated bytes.
+    size: usize,
+    _element: PhantomData<T>,
+}
+
+impl<T: Copy> GpuBuffer<T> {
+    pub fn new(target: BufferTarget, usage: BufferUsage) -> Self {
+        Self { id: create_buffer(), target, usage, len: 0, size: 0, _element: PhantomData }
+    }
+
+    pub fn from_data(target: BufferTarget, data: &[T], usage: BufferUsage) -> Self {
+        let mut buffer = Self::new(target, usage);
+        buffer.upload(data);
+        buffer
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target.gl_target(), self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target.gl_target(), 0);
+        }
+    }
+
+    /// Replaces the contents. The buffer keeps its allocation when `data`
+    /// fits, otherwise it grows.
+    pub fn upload(&mut self, data: &[T]) {
+        self.size = orphan_data(self.id, self.size, data, self.usage);
+        self.len = data.len();
+    }
+
+    /// Overwrites elements starting at `first`. Fails if they don't fit into the allocation.
+    pub fn upload_sub(&mut self, first: usize, data: &[T]) -> Result<(), RendererError> {
+        buffer_sub_data(self.id, self.size, first * size_of::<T>(), data)?;
+        self.len = self.len.max(first + data.len());
+        Ok(())
+    }
+
+    /// Binds the whole buffer to binding point `index` of a uniform or storage target.
+    pub fn bind_base(&self, index: u32) -> Result<(), RendererError> {
+        self.check_indexed()?;
+        unsafe {
+            gl::BindBufferBase(self.target.gl_target(), index, self.id);
+        }
+        Ok(())
+    }
+
+    /// Binds `count` elements starting at `first` to binding point `index`.
+    /// Fails if the range exceeds the buffer or its byte offset doesn't respect
+    /// the driver's offset alignment, see `offset_alignment`.
+    pub fn bind_range(&self, index: u32, first: usize, count: usize) -> Result<(), RendererError> {
+        self.check_indexed()?;
+        let offset = first * size_of::<T>();
+        let size = count * size_of::<T>();
+        if offset + size > self.size {
+            return Err(RendererError::Invalid(format!("Range of {} bytes at {} exceeds the buffer size of {}", size, offset, self.size)));
+        }
+        let alignment = self.offset_alignment();
+        if !offset.is_multiple_of(alignment) {
+            return Err(RendererError::Invalid(format!("Offset {} is not aligned to {} bytes", offset, alignment)));
+        }
+        unsafe {
+            gl::BindBufferRange(self.target.gl_target(), index, self.id, offset as isize, size as isize);
+        }
+        Ok(())
+    }
+
+    fn check_indexed(&self) -> Result<(), RendererError> {
+        if !self.target.is_indexed() {
+            return Err(RendererError::Invalid(format!("{:?} buffers have no binding points", self.target)));
+        }
+        Ok(())
+    }
+
+    /// Required alignment of `bind_range` offsets in bytes.
+    pub fn offset_alignment(&self) -> usize {
+        let pname = match self.target {
+            BufferTarget::Uniform => gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT,
+            BufferTarget::ShaderStorage => gl::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT,
+            _ => return 1,
+        };
+        let mut alignment = 0;
+        unsafe {
+            gl::GetIntegerv(pname, &mut alignment);
+        }
+        alignment.max(1) as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Allocated bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn target(&self) -> BufferTarget {
+        self.target
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl<T: Copy> Drop for GpuBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
 pub struct VBO {
     id: u32,
+    /// Allocated bytes.
+    size: usize,
+    usage: BufferUsage,
 }
 
 pub struct VAO {
@@ -16,29 +208,362 @@ pub struct VAO {
 
 pub struct EBO {
     id: u32,
+    size: usize,
+    usage: BufferUsage,
 }
 
 impl VBO {
-    pub fn buffer_data(&self, vertices: &Vec<Vertex>) {
-        unsafe {
-            gl::BufferData(gl::ARRAY_BUFFER, (size_of::<Vertex>() * vertices.len()) as isize, vertices.as_ptr() as *const _, gl::STATIC_DRAW);
-        }
+    pub fn with_usage(usage: BufferUsage) -> Self {
+        let mut buffer = Self::new();
+        buffer.usage = usage;
+        buffer
+    }
+
+    /// (Re)allocates the buffer with `vertices`.
+    pub fn buffer_data<T: Copy>(&mut self, vertices: &[T], usage: BufferUsage) {
+        buffer_data(self.id, vertices, usage);
+        self.size = size_of_val(vertices);
+        self.usage = usage;
+    }
+
+    /// Overwrites part of the buffer, `offset` is in bytes.
+    pub fn buffer_sub_data<T: Copy>(&self, offset: usize, vertices: &[T]) -> Result<(), RendererError> {
+        buffer_sub_data(self.id, self.size, offset, vertices)
+    }
+
+    /// Replaces the contents of the buffer, see `orphan_data`.
+    pub fn orphan_data<T: Copy>(&mut self, vertices: &[T]) {
+        self.size = orphan_data(self.id, self.size, vertices, self.usage);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.usage
     }
 }
 
 impl VAO {
-    pub fn vertex_attrib_pointer(&self, index: u32, num_components: i32, stride: u32, offset: u32) {
+    /// Attaches `buffer` to vertex buffer binding `binding` and sets up
+    /// `attributes` to read from it. `stride` is in bytes. With a `divisor` of
+    /// 0 the attributes advance per vertex, with 1 per instance.
+    pub fn set_vertex_buffer(&self, binding: u32, buffer: u32, attributes: &[VertexAttribute], stride: usize, divisor: u32) {
+        if gl_caps::dsa() {
+            unsafe {
+                gl::VertexArrayVertexBuffer(self.id, binding, buffer, 0, stride as i32);
+                gl::VertexArrayBindingDivisor(self.id, binding, divisor);
+            }
+            for (location, offset, attribute) in attribute_columns(attributes) {
+                unsafe {
+                    match attribute.kind {
+                        AttributeKind::Integer => gl::VertexArrayAttribIFormat(self.id, location, attribute.components, attribute.component_type.gl_type(), offset as u32),
+                        AttributeKind::Float | AttributeKind::Normalized => {
+                            let normalized = (attribute.kind == AttributeKind::Normalized) as u8;
+                            gl::VertexArrayAttribFormat(self.id, location, attribute.components, attribute.component_type.gl_type(), normalized, offset as u32);
+                        }
+                    }
+                    gl::VertexArrayAttribBinding(self.id, location, binding);
+                    gl::EnableVertexArrayAttrib(self.id, location);
+                }
+            }
+            return;
+        }
+
+        // the buffer used by an attribute is captured by VertexAttribPointer,
+        // `binding` only matters for the DSA path
+        let previous_vao = gl_caps::bound(gl::VERTEX_ARRAY_BINDING);
+        let previous_buffer = gl_caps::bound(gl::ARRAY_BUFFER_BINDING);
         unsafe {
-            gl::VertexAttribPointer(index, num_components, gl::FLOAT, 0, stride as i32 * size_of::<f32>() as i32, (offset * size_of::<f32>() as u32) as *const _);
-            gl::EnableVertexAttribArray(index);
+            gl::BindVertexArray(self.id);
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+        }
+        for (location, offset, attribute) in attribute_columns(attributes) {
+            unsafe {
+                match attribute.kind {
+                    AttributeKind::Integer => gl::VertexAttribIPointer(location, attribute.components, attribute.component_type.gl_type(), stride as i32, offset as *const _),
+                    AttributeKind::Float | AttributeKind::Normalized => {
+                        let normalized = (attribute.kind == AttributeKind::Normalized) as u8;
+                        gl::VertexAttribPointer(location, attribute.components, attribute.component_type.gl_type(), normalized, stride as i32, offset as *const _);
+                    }
+                }
+                gl::VertexAttribDivisor(location, divisor);
+                gl::EnableVertexAttribArray(location);
+            }
+        }
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, previous_buffer);
+            gl::BindVertexArray(previous_vao);
+        }
+    }
+
+    /// Makes `buffer` the index buffer of the VAO, 0 removes it.
+    pub fn set_element_buffer(&self, buffer: u32) {
+        unsafe {
+            if gl_caps::dsa() {
+                gl::VertexArrayElementBuffer(self.id, buffer);
+            } else {
+                // the element binding is VAO state, so the VAO has to be bound first
+                let previous = gl_caps::bound(gl::VERTEX_ARRAY_BINDING);
+                gl::BindVertexArray(self.id);
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffer);
+                gl::BindVertexArray(previous);
+            }
         }
     }
+
+    pub fn disable_attribs(&self, attributes: &[VertexAttribute]) {
+        for (location, _, _) in attribute_columns(attributes) {
+            unsafe {
+                if gl_caps::dsa() {
+                    gl::DisableVertexArrayAttrib(self.id, location);
+                } else {
+                    let previous = gl_caps::bound(gl::VERTEX_ARRAY_BINDING);
+                    gl::BindVertexArray(self.id);
+                    gl::DisableVertexAttribArray(location);
+                    gl::BindVertexArray(previous);
+                }
+            }
+        }
+    }
+}
+
+/// Every location used by `attributes` with its byte offset, matrices take one location per column.
+fn attribute_columns(attributes: &[VertexAttribute]) -> impl Iterator<Item = (u32, usize, &VertexAttribute)> {
+    attributes.iter().flat_map(|attribute| {
+        (0..attribute.columns).map(move |column| {
+            (attribute.location + column, attribute.offset + column as usize * attribute.column_size(), attribute)
+        })
+    })
 }
 
 impl EBO {
-    pub fn buffer_data(&self, indices: &Vec<u32>) {
+    pub fn with_usage(usage: BufferUsage) -> Self {
+        let mut buffer = Self::new();
+        buffer.usage = usage;
+        buffer
+    }
+
+    /// (Re)allocates the buffer with `indices`.
+    pub fn buffer_data(&mut self, indices: &[u32], usage: BufferUsage) {
+        buffer_data(self.id, indices, usage);
+        self.size = size_of_val(indices);
+        self.usage = usage;
+    }
+
+    /// Overwrites part of the buffer, `offset` is in bytes.
+    pub fn buffer_sub_data(&self, offset: usize, indices: &[u32]) -> Result<(), RendererError> {
+        buffer_sub_data(self.id, self.size, offset, indices)
+    }
+
+    /// Replaces the contents of the buffer, see `orphan_data`.
+    pub fn orphan_data(&mut self, indices: &[u32]) {
+        self.size = orphan_data(self.id, self.size, indices, self.usage);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Without DSA buffers are edited through `COPY_WRITE_BUFFER`, which leaves
+/// the array and element bindings, and with them the bound VAO, alone.
+/// Whatever was bound to it is restored afterwards.
+const EDIT_TARGET: u32 = gl::COPY_WRITE_BUFFER;
+const EDIT_BINDING: u32 = gl::COPY_WRITE_BUFFER_BINDING;
+
+fn create_buffer() -> u32 {
+    let mut id = 0;
+    unsafe {
+        if gl_caps::dsa() {
+            gl::CreateBuffers(1, &mut id);
+        } else {
+            gl::GenBuffers(1, &mut id);
+        }
+    }
+    id
+}
+
+fn buffer_data<T: Copy>(id: u32, data: &[T], usage: BufferUsage) {
+    unsafe {
+        if gl_caps::dsa() {
+            gl::NamedBufferData(id, size_of_val(data) as isize, data.as_ptr() as *const _, usage.gl_usage());
+        } else {
+            let previous = gl_caps::bound(EDIT_BINDING);
+                gl::BindBuffer(EDIT_TARGET, id);
+            gl::BufferData(EDIT_TARGET, size_of_val(data) as isize, data.as_ptr() as *const _, usage.gl_usage());
+            gl::BindBuffer(EDIT_TARGET, previous);
+        }
+    }
+}
+
+fn buffer_sub_data<T: Copy>(id: u32, size: usize, offset: usize, data: &[T]) -> Result<(), RendererError> {
+    if offset + size_of_val(data) > size {
+        return Err(RendererError::Invalid(format!("Buffer update of {} bytes at {} exceeds the buffer size of {}", size_of_val(data), offset, size)));
+    }
+    unsafe {
+        if gl_caps::dsa() {
+            gl::NamedBufferSubData(id, offset as isize, size_of_val(data) as isize, data.as_ptr() as *const _);
+        } else {
+            let previous = gl_caps::bound(EDIT_BINDING);
+                gl::BindBuffer(EDIT_TARGET, id);
+            gl::BufferSubData(EDIT_TARGET, offset as isize, size_of_val(data) as isize, data.as_ptr() as *const _);
+            gl::BindBuffer(EDIT_TARGET, previous);
+        }
+    }
+    Ok(())
+}
+
+/// Orphans the old storage, so the driver can hand out fresh memory instead of
+/// waiting for draws still reading it, then writes `data` to the start. The
+/// allocation only grows, so per-frame updates of varying size don't reallocate.
+/// Returns the new size.
+fn orphan_data<T: Copy>(id: u32, size: usize, data: &[T], usage: BufferUsage) -> usize {
+    let size = size.max(size_of_val(data));
+    unsafe {
+        if gl_caps::dsa() {
+            gl::NamedBufferData(id, size as isize, null(), usage.gl_usage());
+            gl::NamedBufferSubData(id, 0, size_of_val(data) as isize, data.as_ptr() as *const _);
+        } else {
+            let previous = gl_caps::bound(EDIT_BINDING);
+                gl::BindBuffer(EDIT_TARGET, id);
+            gl::BufferData(EDIT_TARGET, size as isize, null(), usage.gl_usage());
+            gl::BufferSubData(EDIT_TARGET, 0, size_of_val(data) as isize, data.as_ptr() as *const _);
+            gl::BindBuffer(EDIT_TARGET, previous);
+        }
+    }
+    size
+}
+
+/// Persistently mapped buffer split into sections that are written in turn,
+/// so the CPU can fill one while the GPU still reads the others. A fence per
+/// section keeps the CPU from overwriting data that hasn't been drawn yet.
+/// Needs GL 4.4 or `GL_ARB_buffer_storage`.
+pub struct MappedRing {
+    id: u32,
+    section_size: usize,
+    current: usize,
+    ptr: *mut u8,
+    fences: Vec<Cell<GLsync>>,
+}
+
+impl MappedRing {
+    pub fn new(section_size: usize, sections: usize) -> Result<Self, RendererError> {
+        if !gl_caps::get().buffer_storage {
+            return Err(RendererError::Invalid(String::from("Persistently mapped buffers need GL 4.4 or GL_ARB_buffer_storage")));
+        }
+        let sections = sections.max(1);
+        let total = (section_size * sections).max(1) as isize;
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+        let id = create_buffer();
+        let ptr;
+        unsafe {
+            if gl_caps::dsa() {
+                gl::NamedBufferStorage(id, total, null(), flags);
+                ptr = gl::MapNamedBufferRange(id, 0, total, flags) as *mut u8;
+            } else {
+                let previous = gl_caps::bound(EDIT_BINDING);
+                gl::BindBuffer(EDIT_TARGET, id);
+                gl::BufferStorage(EDIT_TARGET, total, null(), flags);
+                ptr = gl::MapBufferRange(EDIT_TARGET, 0, total, flags) as *mut u8;
+                gl::BindBuffer(EDIT_TARGET, previous);
+            }
+        }
+        if ptr.is_null() {
+            let error = check_gl("Mapping a buffer").err()
+                .unwrap_or_else(|| RendererError::Invalid(format!("Could not map a buffer of {} bytes", total)));
+            unsafe {
+                gl::DeleteBuffers(1, &id);
+            }
+            return Err(error);
+        }
+
+        Ok(Self {
+            id,
+            section_size,
+            current: 0,
+            ptr,
+            fences: (0..sections).map(|_| Cell::new(null())).collect(),
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Moves on to the next section, waiting until the GPU is done with it.
+    pub fn next_section(&mut self) {
+        self.current = (self.current + 1) % self.fences.len();
+        let fence = self.fences[self.current].replace(null());
+        if fence.is_null() {
+            return;
+        }
+        unsafe {
+            loop {
+                let result = gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000);
+                if result != gl::TIMEOUT_EXPIRED {
+                    break;
+                }
+            }
+            gl::DeleteSync(fence);
+        }
+    }
+
+    /// Copies `data` to the start of the current section. Fails if it's larger than a section.
+    pub fn write<T: Copy>(&mut self, data: &[T]) -> Result<(), RendererError> {
+        let size = size_of_val(data);
+        if size > self.section_size {
+            return Err(RendererError::Invalid(format!("{} bytes don't fit into a section of {} bytes", size, self.section_size)));
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, self.ptr.add(self.offset()), size);
+        }
+        Ok(())
+    }
+
+    /// Byte offset of the current section.
+    pub fn offset(&self) -> usize {
+        self.current * self.section_size
+    }
+
+    /// Marks the current section as in use by the commands issued so far.
+    pub fn fence(&self) {
+        unsafe {
+            let old = self.fences[self.current].replace(gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0));
+            if !old.is_null() {
+                gl::DeleteSync(old);
+            }
+        }
+    }
+}
+
+impl Drop for MappedRing {
+    fn drop(&mut self) {
         unsafe {
-            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (size_of::<u32>() * indices.len()) as isize, indices.as_ptr() as *const _, gl::STATIC_DRAW);
+            for fence in self.fences.iter() {
+                if !fence.get().is_null() {
+                    gl::DeleteSync(fence.get());
+                }
+            }
+            if gl_caps::dsa() {
+                gl::UnmapNamedBuffer(self.id);
+            } else {
+                let previous = gl_caps::bound(EDIT_BINDING);
+                gl::BindBuffer(EDIT_TARGET, self.id);
+                gl::UnmapBuffer(EDIT_TARGET);
+                gl::BindBuffer(EDIT_TARGET, previous);
+            }
+            gl::DeleteBuffers(1, &self.id);
         }
     }
 }
@@ -46,12 +571,10 @@ impl EBO {
 
 impl Buffer for VBO {
     fn new() -> Self {
-        let mut id = 0;
-        unsafe {
-            gl::GenBuffers(1, &mut id);
-        }
         Self {
-            id
+            id: create_buffer(),
+            size: 0,
+            usage: BufferUsage::Static,
         }
     }
     
@@ -72,7 +595,11 @@ impl Buffer for VAO {
     fn new() -> Self {
         let mut id = 0;
         unsafe {
-            gl::GenVertexArrays(1, &mut id);
+            if gl_caps::dsa() {
+                gl::CreateVertexArrays(1, &mut id);
+            } else {
+                gl::GenVertexArrays(1, &mut id);
+            }
         }
         Self {
             id
@@ -94,12 +621,10 @@ impl Buffer for VAO {
 
 impl Buffer for EBO {
     fn new() -> Self {
-        let mut id = 0;
-        unsafe {
-            gl::GenBuffers(1, &mut id);
-        }
         Self {
-            id
+            id: create_buffer(),
+            size: 0,
+            usage: BufferUsage::Static,
         }
     }
     
@@ -1,6 +1,20 @@
-use glm::{cos, cross, normalize, sin, Mat4, Vec3};
+use glm::{cos, cross, normalize, sin, Mat4, Vec3, Vec4};
 use num_traits::One;
 
+/// Binding point of the `Camera` uniform block in shaders.
+pub const CAMERA_BINDING: u32 = 0;
+
+/// Contents of the `Camera` uniform block (std140).
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CameraUniforms {
+    pub projection: Mat4,
+    pub view: Mat4,
+    pub pv: Mat4,
+    /// w is unused
+    pub position: Vec4,
+}
+
 pub struct Camera {
     position: Vec3,
     rotation: Vec3,
@@ -53,6 +67,15 @@ impl Camera {
         self.pv_mat
     }
     
+    pub fn uniforms(&self) -> CameraUniforms {
+        CameraUniforms {
+            projection: self.proj_mat,
+            view: self.view_mat,
+            pv: self.pv_mat,
+            position: self.position.extend(1.0),
+        }
+    }
+    
     pub fn forward(&self) -> Vec3 {
         let forward = Vec3::new(
             cos(self.rotation.y) * cos(self.rotation.x),
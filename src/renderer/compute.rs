@@ -0,0 +1,65 @@
+use std::ops::BitOr;
+
+/// Which kinds of access have to see writes made by earlier shader invocations,
+/// e.g. from a compute dispatch. Combine them with `|`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrier(u32);
+
+impl Barrier {
+    /// Vertex attributes sourced from buffers written by a shader.
+    pub const VERTEX_ATTRIB_ARRAY: Self = Self(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: Self = Self(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: Self = Self(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: Self = Self(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE_ACCESS: Self = Self(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    /// Indirect draw and dispatch parameters.
+    pub const COMMAND: Self = Self(gl::COMMAND_BARRIER_BIT);
+    pub const PIXEL_BUFFER: Self = Self(gl::PIXEL_BUFFER_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: Self = Self(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    /// Reads and writes through `BufferSubData`, mapping and copies.
+    pub const BUFFER_UPDATE: Self = Self(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: Self = Self(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const ATOMIC_COUNTER: Self = Self(gl::ATOMIC_COUNTER_BARRIER_BIT);
+    pub const SHADER_STORAGE: Self = Self(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: Self = Self(gl::ALL_BARRIER_BITS);
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+impl BitOr for Barrier {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Makes shader writes visible to the accesses in `barriers` of later commands.
+pub fn memory_barrier(barriers: Barrier) {
+    unsafe {
+        gl::MemoryBarrier(barriers.bits());
+    }
+}
+
+/// Like `memory_barrier`, but only orders accesses from fragment shaders to
+/// the same framebuffer region, which can be cheaper.
+pub fn memory_barrier_by_region(barriers: Barrier) {
+    unsafe {
+        gl::MemoryBarrierByRegion(barriers.bits());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barriers_combine_their_bits() {
+        let barriers = Barrier::VERTEX_ATTRIB_ARRAY | Barrier::SHADER_STORAGE;
+        assert_eq!(barriers.bits(), gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT | gl::SHADER_STORAGE_BARRIER_BIT);
+        assert_eq!(barriers | Barrier::ALL, Barrier::ALL);
+        assert_eq!(Barrier::UNIFORM | Barrier::UNIFORM, Barrier::UNIFORM);
+    }
+}
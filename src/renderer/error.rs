@@ -0,0 +1,92 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::renderer::shader::ShaderError;
+
+/// Everything that can go wrong while loading assets or creating GL objects.
+#[derive(Debug)]
+pub enum RendererError {
+    /// A file couldn't be read.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// An image file couldn't be decoded.
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    Shader(ShaderError),
+    /// `glGetError` or a status check reported `code` after `operation`.
+    Gl {
+        operation: &'static str,
+        code: u32,
+    },
+    /// An OBJ, MTL or glTF file is malformed. `line` is 1-based where the format has lines.
+    Asset {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A mesh doesn't supply the vertex inputs a shader reads.
+    Layout(String),
+    /// Data or arguments the renderer can't use.
+    Invalid(String),
+}
+
+impl RendererError {
+    pub fn io(path: &Path, source: std::io::Error) -> Self {
+        RendererError::Io { path: path.to_path_buf(), source }
+    }
+
+    /// Decode errors that are really I/O errors become `Io`.
+    pub fn image(path: &Path, source: image::ImageError) -> Self {
+        match source {
+            image::ImageError::IoError(source) => Self::io(path, source),
+            source => RendererError::Image { path: path.to_path_buf(), source },
+        }
+    }
+
+    pub fn asset<M: Into<String>>(path: &Path, line: Option<usize>, message: M) -> Self {
+        RendererError::Asset { path: path.to_path_buf(), line, message: message.into() }
+    }
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::Io { path, source } => write!(f, "Could not read {}: {}", path.display(), source),
+            RendererError::Image { path, source } => write!(f, "Could not decode {}: {}", path.display(), source),
+            RendererError::Shader(error) => write!(f, "{}", error),
+            RendererError::Gl { operation, code } => write!(f, "{} failed with GL error 0x{:x}", operation, code),
+            RendererError::Asset { path, line: Some(line), message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            RendererError::Asset { path, line: None, message } => write!(f, "{}: {}", path.display(), message),
+            RendererError::Layout(message) | RendererError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::Io { source, .. } => Some(source),
+            RendererError::Image { source, .. } => Some(source),
+            RendererError::Shader(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ShaderError> for RendererError {
+    fn from(error: ShaderError) -> Self {
+        RendererError::Shader(error)
+    }
+}
+
+/// Returns the pending GL error, if any, as a `Gl` error for `operation`.
+pub fn check_gl(operation: &'static str) -> Result<(), RendererError> {
+    let code = unsafe { gl::GetError() };
+    if code != gl::NO_ERROR {
+        return Err(RendererError::Gl { operation, code });
+    }
+    Ok(())
+}
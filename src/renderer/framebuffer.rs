@@ -0,0 +1,167 @@
+use crate::renderer::error::RendererError;
+use crate::renderer::gl_caps;
+use crate::renderer::texture::Texture;
+use crate::renderer::texture_format::TextureFormat;
+
+/// Off-screen render target with colour textures and an optional depth
+/// texture. Attachments are recreated on `resize`.
+pub struct Framebuffer {
+    id: u32,
+    color_formats: Vec<TextureFormat>,
+    depth_format: Option<TextureFormat>,
+    colors: Vec<Texture>,
+    depth: Option<Texture>,
+    width: i32,
+    height: i32,
+}
+
+impl Framebuffer {
+    /// `color_attachments` RGBA8 textures and, if `depth` is set, a depth-stencil texture.
+    pub fn new(width: i32, height: i32, color_attachments: usize, depth: bool) -> Result<Self, RendererError> {
+        let color_formats = vec![TextureFormat::Rgba8; color_attachments];
+        Self::with_formats(width, height, &color_formats, depth.then_some(TextureFormat::Depth24Stencil8))
+    }
+
+    /// One colour texture per entry of `color_formats`, e.g. `Rgba16F` for HDR,
+    /// and a depth texture if `depth_format` is set.
+    pub fn with_formats(width: i32, height: i32, color_formats: &[TextureFormat], depth_format: Option<TextureFormat>) -> Result<Self, RendererError> {
+        if let Some(format) = color_formats.iter().find(|format| format.is_depth()) {
+            return Err(RendererError::Invalid(format!("{:?} can't be used as a colour attachment", format)));
+        }
+        if let Some(format) = depth_format.filter(|format| !format.is_depth()) {
+            return Err(RendererError::Invalid(format!("{:?} can't be used as a depth attachment", format)));
+        }
+
+        let mut id = 0;
+        unsafe {
+            if gl_caps::dsa() {
+                gl::CreateFramebuffers(1, &mut id);
+            } else {
+                gl::GenFramebuffers(1, &mut id);
+            }
+        }
+
+        let mut framebuffer = Self {
+            id,
+            color_formats: color_formats.to_vec(),
+            depth_format,
+            colors: Vec::new(),
+            depth: None,
+            width,
+            height,
+        };
+        framebuffer.attach()?;
+        Ok(framebuffer)
+    }
+
+    /// Renders into the framebuffer from now on and sets the viewport to its size.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.width, self.height);
+        }
+    }
+
+    /// Goes back to the default framebuffer. The viewport has to be restored by the caller.
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), RendererError> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        self.width = width;
+        self.height = height;
+        self.depth = None;
+        self.colors.clear();
+        self.attach()
+    }
+
+    pub fn color(&self, index: usize) -> &Texture {
+        &self.colors[index]
+    }
+
+    pub fn depth(&self) -> Option<&Texture> {
+        self.depth.as_ref()
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn attach(&mut self) -> Result<(), RendererError> {
+        if gl_caps::dsa() {
+            return self.create_attachments(true);
+        }
+        let previous_draw = gl_caps::bound(gl::DRAW_FRAMEBUFFER_BINDING);
+        let previous_read = gl_caps::bound(gl::READ_FRAMEBUFFER_BINDING);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+        let result = self.create_attachments(false);
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous_draw);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read);
+        }
+        result
+    }
+
+    /// Creates and attaches the textures. Without DSA the framebuffer has to be bound.
+    fn create_attachments(&mut self, dsa: bool) -> Result<(), RendererError> {
+        let attach_texture = |attachment: u32, texture: &Texture| unsafe {
+            if dsa {
+                gl::NamedFramebufferTexture(self.id, attachment, texture.id(), 0);
+            } else {
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture.id(), 0);
+            }
+        };
+
+        let mut draw_buffers = Vec::with_capacity(self.color_formats.len());
+        let mut colors = Vec::with_capacity(self.color_formats.len());
+        for (i, format) in self.color_formats.iter().enumerate() {
+            let texture = Texture::empty_with_format(self.width, self.height, *format)?;
+            let attachment = gl::COLOR_ATTACHMENT0 + i as u32;
+            attach_texture(attachment, &texture);
+            draw_buffers.push(attachment);
+            colors.push(texture);
+        }
+
+        let mut depth = None;
+        if let Some(format) = self.depth_format {
+            let texture = Texture::empty_with_format(self.width, self.height, format)?;
+            attach_texture(format.depth_attachment().unwrap_or(gl::DEPTH_ATTACHMENT), &texture);
+            depth = Some(texture);
+        }
+        self.colors = colors;
+        self.depth = depth;
+
+        unsafe {
+            let status = if dsa {
+                gl::NamedFramebufferDrawBuffers(self.id, draw_buffers.len() as i32, draw_buffers.as_ptr());
+                gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER)
+            } else {
+                gl::DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+                gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(RendererError::Gl { operation: "Framebuffer completeness check", code: status });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::ffi::CStr;
+use std::sync::OnceLock;
+
+/// What the current GL context supports. Detected once by `init` after the
+/// function pointers are loaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlCaps {
+    pub major: i32,
+    pub minor: i32,
+    /// Direct state access, core in 4.5 or through `GL_ARB_direct_state_access`.
+    pub dsa: bool,
+    /// `glGetProgramBinary` with at least one binary format, core in 4.1.
+    pub program_binary: bool,
+    /// Immutable buffer storage and persistent mapping, core in 4.4 or through
+    /// `GL_ARB_buffer_storage`.
+    pub buffer_storage: bool,
+    /// SPIR-V shader modules, core in 4.6 or through `GL_ARB_gl_spirv`.
+    pub spirv: bool,
+    /// Highest anisotropy for texture filtering, core in 4.6 or through
+    /// `GL_ARB/EXT_texture_filter_anisotropic`. 1 if unsupported.
+    pub max_anisotropy: f32,
+}
+
+/// `GL_MAX_TEXTURE_MAX_ANISOTROPY`, not covered by the `gl` bindings.
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+static CAPS: OnceLock<GlCaps> = OnceLock::new();
+
+/// Queries the context. Has to be called after `gl::load_with` and before
+/// creating any GL resources.
+pub fn init() -> GlCaps {
+    *CAPS.get_or_init(detect)
+}
+
+/// The detected capabilities. Calling this before `init` is a bug caught in
+/// debug builds; release builds assume everything is missing, so resources
+/// take the bind-to-edit path.
+pub fn get() -> GlCaps {
+    debug_assert!(CAPS.get().is_some(), "gl_caps::get called before gl_caps::init");
+    CAPS.get().copied().unwrap_or(GlCaps { major: 0, minor: 0, dsa: false, program_binary: false, buffer_storage: false, spirv: false, max_anisotropy: 1.0 })
+}
+
+pub fn dsa() -> bool {
+    get().dsa
+}
+
+/// The object bound to `binding`, e.g. `gl::ARRAY_BUFFER_BINDING`, so the
+/// bind-to-edit paths can restore it afterwards.
+pub fn bound(binding: u32) -> u32 {
+    let mut id = 0;
+    unsafe {
+        gl::GetIntegerv(binding, &mut id);
+    }
+    id as u32
+}
+
+/// Vendor, renderer and version string of the driver, e.g. to tell whether
+/// cached program binaries were made by the same driver.
+pub fn driver() -> String {
+    let get = |name| unsafe {
+        let ptr = gl::GetString(name);
+        if ptr.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(ptr as *const _).to_string_lossy().into_owned()
+        }
+    };
+    format!("{} / {} / {}", get(gl::VENDOR), get(gl::RENDERER), get(gl::VERSION))
+}
+
+fn detect() -> GlCaps {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    let dsa = (major, minor) >= (4, 5) || has_extension("GL_ARB_direct_state_access");
+    let mut binary_formats = 0;
+    if (major, minor) >= (4, 1) || has_extension("GL_ARB_get_program_binary") {
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut binary_formats);
+        }
+    }
+    let buffer_storage = (major, minor) >= (4, 4) || has_extension("GL_ARB_buffer_storage");
+    let spirv = (major, minor) >= (4, 6) || has_extension("GL_ARB_gl_spirv");
+    let mut max_anisotropy = 1.0;
+    if (major, minor) >= (4, 6) || has_extension("GL_ARB_texture_filter_anisotropic") || has_extension("GL_EXT_texture_filter_anisotropic") {
+        unsafe {
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+        }
+    }
+    GlCaps { major, minor, dsa, program_binary: binary_formats > 0, buffer_storage, spirv, max_anisotropy }
+}
+
+fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+    (0..count.max(0) as u32).any(|i| {
+        let ptr = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+        !ptr.is_null() && unsafe { CStr::from_ptr(ptr as *const _) }.to_bytes() == name.as_bytes()
+    })
+}
@@ -0,0 +1,155 @@
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::rc::Rc;
+use glm::{Mat4, Vec4};
+use num_traits::{One, Zero};
+use crate::renderer::buffer::{BufferTarget, BufferUsage, GpuBuffer};
+use crate::renderer::error::RendererError;
+use crate::renderer::material::Material;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::model::Transform;
+use crate::renderer::shader::Shader;
+use crate::renderer::vertex_layout::{vertex_layout, VertexAttribute, VertexLayout};
+
+vertex_layout! {
+    /// Per-instance attributes read by `instanced.vert`. The model matrix takes
+    /// locations 8 to 11. `custom` is free for shaders to use.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct InstanceData {
+        #[location(8)] pub model: Mat4,
+        #[location(12)] pub tint: Vec4,
+        #[location(13)] pub custom: Vec4,
+    }
+}
+
+impl InstanceData {
+    pub fn new(model: Mat4) -> Self {
+        Self { model, tint: Vec4::one(), custom: Vec4::zero() }
+    }
+
+    pub fn from_transform(transform: &Transform) -> Self {
+        Self::new(transform.model_matrix())
+    }
+
+    pub fn with_tint(mut self, tint: Vec4) -> Self {
+        self.tint = tint;
+        self
+    }
+}
+
+/// GPU buffer of per-instance data, drawn with `Mesh::render_instanced`.
+pub struct InstanceBuffer<I: VertexLayout = InstanceData> {
+    buffer: GpuBuffer<I>,
+}
+
+impl<I: VertexLayout> InstanceBuffer<I> {
+    pub fn new() -> Self {
+        Self { buffer: GpuBuffer::new(BufferTarget::Array, BufferUsage::Stream) }
+    }
+
+    /// Replaces the contents of the buffer.
+    pub fn update(&mut self, instances: &[I]) {
+        self.buffer.upload(instances);
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.buffer.id()
+    }
+}
+
+/// Draws the same mesh many times in a single draw call, e.g. for a forest of
+/// trees. Edit the instances with `instances_mut`, the buffer is updated on the
+/// next `render`. The shader is expected to take the instance attributes like
+/// `instanced.vert` does, and either the camera block or a `pv` uniform instead of `pvm`.
+pub struct InstancedModel {
+    mesh: Rc<RefCell<Mesh>>,
+    shader: Rc<RefCell<Shader>>,
+    material: Material,
+
+    instances: Vec<InstanceData>,
+    buffer: RefCell<InstanceBuffer>,
+    dirty: Cell<bool>,
+}
+
+impl InstancedModel {
+    pub fn new(mesh: Rc<RefCell<Mesh>>, shader: Rc<RefCell<Shader>>, instances: Vec<InstanceData>) -> Self {
+        Self::with_material(mesh, shader, instances, Material::default())
+    }
+
+    pub fn with_material(mesh: Rc<RefCell<Mesh>>, shader: Rc<RefCell<Shader>>, instances: Vec<InstanceData>, material: Material) -> Self {
+        Self {
+            mesh,
+            shader,
+            material,
+            instances,
+            buffer: RefCell::new(InstanceBuffer::new()),
+            dirty: Cell::new(true),
+        }
+    }
+
+    /// Checks that the mesh and the instance data together supply every vertex input the shader reads.
+    pub fn check_layout(&self) -> Result<(), RendererError> {
+        let mesh = self.mesh.borrow();
+        let attributes: Vec<VertexAttribute> = mesh.layout().iter().chain(InstanceData::ATTRIBUTES).copied().collect();
+        self.shader.borrow().reflection().check_vertex_layout(&attributes)
+    }
+
+    /// Draws every instance, fails without drawing if a uniform has a different type in the shader.
+    pub fn render(&self, pv_mat: Mat4) -> Result<(), RendererError> {
+        if self.dirty.replace(false) {
+            self.buffer.borrow_mut().update(&self.instances);
+        }
+
+        {
+            let mut shader = self.shader.borrow_mut();
+            shader.bind();
+            // shaders using the camera uniform block don't need it
+            shader.set("pv", pv_mat)?;
+            self.material.apply(&mut shader)?;
+        }
+
+        self.mesh.borrow().render_instanced(&self.buffer.borrow());
+        Ok(())
+    }
+
+    pub fn instances(&self) -> &[InstanceData] {
+        &self.instances
+    }
+
+    pub fn instances_mut(&mut self) -> &mut Vec<InstanceData> {
+        self.dirty.set(true);
+        &mut self.instances
+    }
+
+    pub fn set_instances(&mut self, instances: Vec<InstanceData>) {
+        self.instances = instances;
+        self.dirty.set(true);
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    pub fn mesh(&self) -> Ref<'_, Mesh> {
+        self.mesh.borrow()
+    }
+
+    pub fn shader(&self) -> Ref<'_, Shader> {
+        self.shader.borrow()
+    }
+
+    pub fn shader_mut(&self) -> RefMut<'_, Shader> {
+        self.shader.borrow_mut()
+    }
+}
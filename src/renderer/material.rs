@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use glm::{Vec3, Vec4};
+use crate::renderer::error::RendererError;
+use crate::renderer::mtl::MtlMaterial;
+use crate::renderer::sampler::SamplerDesc;
+use crate::renderer::shader::Shader;
+use crate::renderer::shader_variants::Defines;
+use crate::renderer::texture::{Texture, TextureUnit};
+use crate::renderer::texture_format::ColorSpace;
+
+/// The textures a material can use. Each has a fixed texture unit and
+/// sampler uniform, and a define so shader variants only sample what exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MaterialMap {
+    Diffuse,
+    Normal,
+    Specular,
+    /// Roughness in green, metallic in blue, as in glTF.
+    MetallicRoughness,
+    Emissive,
+}
+
+impl MaterialMap {
+    pub const ALL: [MaterialMap; 5] = [
+        MaterialMap::Diffuse,
+        MaterialMap::Normal,
+        MaterialMap::Specular,
+        MaterialMap::MetallicRoughness,
+        MaterialMap::Emissive,
+    ];
+
+    /// Texture unit the map is bound to. Extra textures of a material use the units after these.
+    pub fn unit(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn uniform(&self) -> &'static str {
+        match self {
+            MaterialMap::Diffuse => "material_diffuse_map",
+            MaterialMap::Normal => "material_normal_map",
+            MaterialMap::Specular => "material_specular_map",
+            MaterialMap::MetallicRoughness => "material_metallic_roughness_map",
+            MaterialMap::Emissive => "material_emissive_map",
+        }
+    }
+
+    /// Colour maps are sRGB, the others hold data.
+    pub fn color_space(&self) -> ColorSpace {
+        match self {
+            MaterialMap::Diffuse | MaterialMap::Emissive => ColorSpace::Srgb,
+            _ => ColorSpace::Linear,
+        }
+    }
+
+    pub fn define(&self) -> &'static str {
+        match self {
+            MaterialMap::Diffuse => "HAS_DIFFUSE_MAP",
+            MaterialMap::Normal => "HAS_NORMAL_MAP",
+            MaterialMap::Specular => "HAS_SPECULAR_MAP",
+            MaterialMap::MetallicRoughness => "HAS_METALLIC_ROUGHNESS_MAP",
+            MaterialMap::Emissive => "HAS_EMISSIVE_MAP",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub opacity: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vec3,
+    /// Fragments with a lower alpha are discarded, `None` blends instead.
+    pub alpha_cutoff: Option<f32>,
+
+    pub diffuse_map: Option<Rc<Texture>>,
+    pub bump_map: Option<Rc<Texture>>,
+    pub specular_map: Option<Rc<Texture>>,
+    pub metallic_roughness_map: Option<Rc<Texture>>,
+    pub emissive_map: Option<Rc<Texture>>,
+    /// Textures for other sampler uniforms of the shader, as `(uniform, texture)`.
+    pub textures: Vec<(String, Rc<Texture>)>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            diffuse: Vec3::new(1.0, 1.0, 1.0),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 32.0,
+            opacity: 1.0,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: Vec3::new(0.0, 0.0, 0.0),
+            alpha_cutoff: None,
+            diffuse_map: None,
+            bump_map: None,
+            specular_map: None,
+            metallic_roughness_map: None,
+            emissive_map: None,
+            textures: Vec::new(),
+        }
+    }
+}
+
+impl Material {
+    /// Creates a material from parsed MTL data and loads the textures it references.
+    /// Textures are shared through `textures` so maps used by several materials are only uploaded once.
+    /// Textures that are missing or can't be loaded are skipped, their errors are added to `warnings`.
+    pub fn from_mtl(mtl: &MtlMaterial, textures: &mut HashMap<(PathBuf, ColorSpace), Rc<Texture>>, warnings: &mut Vec<RendererError>) -> Self {
+        Self {
+            name: mtl.name.clone(),
+            diffuse: mtl.diffuse,
+            specular: mtl.specular,
+            shininess: mtl.shininess,
+            opacity: mtl.opacity,
+            emissive: mtl.emissive,
+            diffuse_map: load_texture(mtl.diffuse_map.as_deref(), MaterialMap::Diffuse, textures, warnings),
+            bump_map: load_texture(mtl.bump_map.as_deref(), MaterialMap::Normal, textures, warnings),
+            specular_map: load_texture(mtl.specular_map.as_deref(), MaterialMap::Specular, textures, warnings),
+            emissive_map: load_texture(mtl.emissive_map.as_deref(), MaterialMap::Emissive, textures, warnings),
+            ..Self::default()
+        }
+    }
+
+    /// Binds `texture` to the sampler uniform `uniform`, replacing the texture it had.
+    pub fn with_texture(mut self, uniform: &str, texture: Rc<Texture>) -> Self {
+        self.set_texture(uniform, texture);
+        self
+    }
+
+    pub fn set_texture(&mut self, uniform: &str, texture: Rc<Texture>) {
+        match self.textures.iter_mut().find(|(known, _)| known == uniform) {
+            Some((_, known)) => *known = texture,
+            None => self.textures.push((uniform.to_string(), texture)),
+        }
+    }
+
+    pub fn map(&self, map: MaterialMap) -> Option<&Rc<Texture>> {
+        match map {
+            MaterialMap::Diffuse => self.diffuse_map.as_ref(),
+            MaterialMap::Normal => self.bump_map.as_ref(),
+            MaterialMap::Specular => self.specular_map.as_ref(),
+            MaterialMap::MetallicRoughness => self.metallic_roughness_map.as_ref(),
+            MaterialMap::Emissive => self.emissive_map.as_ref(),
+        }
+    }
+
+    /// Uploads the material parameters to `shader` and binds every texture to
+    /// its unit and sampler uniform. Uniforms the shader doesn't declare are
+    /// skipped, their textures aren't bound. Fails on the first uniform of a
+    /// different type.
+    pub fn apply(&self, shader: &mut Shader) -> Result<(), RendererError> {
+        shader.set("material_diffuse", Vec4::new(self.diffuse.x, self.diffuse.y, self.diffuse.z, self.opacity))?;
+        shader.set("material_specular", self.specular)?;
+        shader.set("material_shininess", self.shininess)?;
+        shader.set("material_metallic", self.metallic)?;
+        shader.set("material_roughness", self.roughness)?;
+        shader.set("material_emissive", self.emissive)?;
+        if let Some(cutoff) = self.alpha_cutoff {
+            shader.set("material_alpha_cutoff", cutoff)?;
+        }
+
+        for map in MaterialMap::ALL {
+            if let Some(texture) = self.map(map) {
+                shader.set(map.uniform(), TextureUnit::new(texture, map.unit()))?;
+            }
+        }
+        let first_unit = MaterialMap::ALL.len() as u32;
+        for (unit, (uniform, texture)) in (first_unit..).zip(self.textures.iter()) {
+            shader.set(uniform, TextureUnit::new(texture, unit))?;
+        }
+        Ok(())
+    }
+
+    /// Shader defines for the features this material uses, used to pick a
+    /// variant from `ShaderVariants`.
+    pub fn defines(&self) -> Defines {
+        let mut defines = Defines::new();
+        for map in MaterialMap::ALL {
+            if self.map(map).is_some() {
+                defines.set(map.define(), "");
+            }
+        }
+        if self.alpha_cutoff.is_some() {
+            defines.set("ALPHA_TEST", "");
+        }
+        defines
+    }
+}
+
+fn load_texture(path: Option<&Path>, map: MaterialMap, textures: &mut HashMap<(PathBuf, ColorSpace), Rc<Texture>>, warnings: &mut Vec<RendererError>) -> Option<Rc<Texture>> {
+    let key = (path?.to_path_buf(), map.color_space());
+
+    if let Some(texture) = textures.get(&key) {
+        return Some(texture.clone());
+    }
+
+    match Texture::load(&key.0, key.1, SamplerDesc::default()) {
+        Ok(texture) => {
+            let texture = Rc::new(texture);
+            textures.insert(key, texture.clone());
+            Some(texture)
+        }
+        Err(e) => {
+            warnings.push(e);
+            None
+        }
+    }
+}
@@ -1,161 +1,324 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::ptr::null;
-use glm::{Vec2, Vec3, Vec4};
-use crate::renderer::buffer::{Buffer, EBO, VAO, VBO};
-use crate::renderer::vertex::Vertex;
+use std::rc::Rc;
+use crate::renderer::buffer::{Buffer, BufferUsage, EBO, VAO, VBO};
+use crate::renderer::error::RendererError;
+use crate::renderer::instancing::InstanceBuffer;
+use crate::renderer::material::Material;
+use crate::renderer::texture::Texture;
+use crate::renderer::texture_format::ColorSpace;
+use crate::renderer::{mtl, obj};
+use crate::renderer::mesh_data::{MeshData, DEFAULT_CREASE_ANGLE};
+use crate::renderer::vertex_layout::{VertexAttribute, VertexLayout};
+
+/// Vertex buffer binding of the per-vertex data.
+const VERTEX_BINDING: u32 = 0;
+/// Vertex buffer binding of the per-instance data.
+const INSTANCE_BINDING: u32 = 1;
+
+/// How vertices are assembled into primitives when drawing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Topology {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    /// Groups of this many vertices, for tessellation shaders.
+    Patches(u8),
+}
+
+impl Topology {
+    pub fn gl_mode(&self) -> u32 {
+        match self {
+            Topology::Points => gl::POINTS,
+            Topology::Lines => gl::LINES,
+            Topology::LineStrip => gl::LINE_STRIP,
+            Topology::LineLoop => gl::LINE_LOOP,
+            Topology::Triangles => gl::TRIANGLES,
+            Topology::TriangleStrip => gl::TRIANGLE_STRIP,
+            Topology::TriangleFan => gl::TRIANGLE_FAN,
+            Topology::Patches(_) => gl::PATCHES,
+        }
+    }
+
+    /// Sets the patch size for patch topologies, has to happen before every draw
+    /// since it is global state.
+    pub fn set_patch_vertices(&self) {
+        if let Topology::Patches(vertices) = self {
+            unsafe {
+                gl::PatchParameteri(gl::PATCH_VERTICES, *vertices as i32);
+            }
+        }
+    }
+
+    pub fn is_triangles(&self) -> bool {
+        matches!(self, Topology::Triangles | Topology::TriangleStrip | Topology::TriangleFan)
+    }
+
+    pub fn is_lines(&self) -> bool {
+        matches!(self, Topology::Lines | Topology::LineStrip | Topology::LineLoop)
+    }
+
+    /// The topology strips, loops and fans unroll to.
+    pub fn list(&self) -> Topology {
+        if self.is_triangles() {
+            Topology::Triangles
+        } else if self.is_lines() {
+            Topology::Lines
+        } else if let Topology::Patches(_) = self {
+            *self
+        } else {
+            Topology::Points
+        }
+    }
+}
 
 #[allow(dead_code)]
 pub struct Mesh {
-    ebo: EBO,
+    /// `None` for meshes drawn with `DrawArrays`.
+    ebo: Option<EBO>,
     vbo: VBO,
     vao: VAO,
     
-    indices_len: usize,
+    vertex_count: usize,
+    index_count: usize,
+    topology: Topology,
+    layout: &'static [VertexAttribute],
+}
+
+/// One material section of a model file, ready to be put into a `Model`.
+pub struct SubMesh {
+    pub mesh: Mesh,
+    pub material: Material,
+}
+
+/// The submeshes of a model file and what went wrong loading it without
+/// making it unusable, e.g. missing material libraries or textures.
+pub struct LoadedModel {
+    pub submeshes: Vec<SubMesh>,
+    pub warnings: Vec<RendererError>,
 }
 
 impl Mesh {
-    pub fn new(vertices: &Vec<Vertex>, indices: &Vec<u32>) -> Self {
-        let ebo = EBO::new();
-        let vbo = VBO::new();
+    /// Uploads `data` to the GPU.
+    pub fn new(data: &MeshData) -> Self {
+        Self::with_usage(data, BufferUsage::Static)
+    }
+
+    /// Uploads `data` for a mesh that is going to be updated with `usage` in mind.
+    pub fn with_usage(data: &MeshData, usage: BufferUsage) -> Self {
+        Self::from_vertices(&data.vertices, &data.indices, data.topology, usage)
+    }
+
+    /// Uploads vertices of any layout, the attributes are set up from `V::ATTRIBUTES`.
+    /// Without indices the mesh is drawn with `DrawArrays`.
+    pub fn from_vertices<V: VertexLayout>(vertices: &[V], indices: &[u32], topology: Topology, usage: BufferUsage) -> Self {
         let vao = VAO::new();
+        let mut vbo = VBO::new();
+        vbo.buffer_data(vertices, usage);
+        vao.set_vertex_buffer(VERTEX_BINDING, vbo.id(), V::ATTRIBUTES, size_of::<V>(), 0);
         
-        ebo.bind();
-        ebo.buffer_data(&indices);
-        
-        vao.bind();
-        vbo.bind();
-        vbo.buffer_data(vertices);
-        vao.vertex_attrib_pointer(0, 3, 8, 0);
-        vao.vertex_attrib_pointer(1, 3, 8, 3);
-        vao.vertex_attrib_pointer(2, 2, 8, 6);
-        
-        ebo.unbind();
-        vbo.unbind();
-        vao.unbind();
+        let ebo = (!indices.is_empty()).then(|| {
+            let mut ebo = EBO::new();
+            ebo.buffer_data(indices, usage);
+            vao.set_element_buffer(ebo.id());
+            ebo
+        });
         
         Self {
             ebo,
             vbo,
             vao,
-            indices_len: indices.len()
+            vertex_count: vertices.len(),
+            index_count: indices.len(),
+            topology,
+            layout: V::ATTRIBUTES,
         }
     }
+
+    /// Overwrites vertices starting at `first`, the rest of the buffer is kept.
+    /// `V` has to be the layout the mesh was created with.
+    pub fn update_vertices<V: VertexLayout>(&mut self, first: usize, vertices: &[V]) -> Result<(), RendererError> {
+        self.check_layout::<V>()?;
+        self.vbo.buffer_sub_data(first * size_of::<V>(), vertices)
+    }
+
+    /// Replaces all vertices, their number may change. The buffer is orphaned
+    /// so this doesn't stall on draws that still use the old data.
+    pub fn set_vertices<V: VertexLayout>(&mut self, vertices: &[V]) -> Result<(), RendererError> {
+        self.check_layout::<V>()?;
+        self.vbo.orphan_data(vertices);
+        self.vertex_count = vertices.len();
+        Ok(())
+    }
+
+    /// Overwrites indices starting at `first`. The mesh has to be indexed.
+    pub fn update_indices(&mut self, first: usize, indices: &[u32]) -> Result<(), RendererError> {
+        let ebo = self.ebo.as_ref()
+            .ok_or_else(|| RendererError::Invalid(String::from("Can't update the indices of a non-indexed mesh")))?;
+        ebo.buffer_sub_data(first * size_of::<u32>(), indices)
+    }
+
+    /// Replaces all indices. Without indices the mesh is drawn with `DrawArrays`.
+    pub fn set_indices(&mut self, indices: &[u32]) {
+        self.index_count = indices.len();
+        if indices.is_empty() {
+            self.vao.set_element_buffer(0);
+            self.ebo = None;
+            return;
+        }
+
+        match &mut self.ebo {
+            Some(ebo) => ebo.orphan_data(indices),
+            None => {
+                let mut ebo = EBO::new();
+                ebo.buffer_data(indices, self.vbo.usage());
+                self.vao.set_element_buffer(ebo.id());
+                self.ebo = Some(ebo);
+            }
+        }
+    }
+
+    /// Replaces the whole geometry with `data`.
+    pub fn update(&mut self, data: &MeshData) -> Result<(), RendererError> {
+        self.set_vertices(&data.vertices)?;
+        self.set_indices(&data.indices);
+        self.topology = data.topology;
+        Ok(())
+    }
+
+    pub fn usage(&self) -> BufferUsage {
+        self.vbo.usage()
+    }
+
+    fn check_layout<V: VertexLayout>(&self) -> Result<(), RendererError> {
+        if V::ATTRIBUTES != self.layout {
+            return Err(RendererError::Invalid(String::from("Vertex layout doesn't match the one the mesh was created with")));
+        }
+        Ok(())
+    }
+
+    /// Number of indices, or of vertices for non-indexed meshes.
+    fn count(&self) -> usize {
+        if self.ebo.is_some() { self.index_count } else { self.vertex_count }
+    }
     
-    pub fn from_model(path: &PathBuf) -> Result<Self, String> {
-        let (vertices, indices) = load_from_obj(path)?;
-        Ok(Mesh::new(&vertices, &indices))
+    pub fn from_model(path: &Path) -> Result<Self, RendererError> {
+        Ok(MeshData::from_obj(path)?.upload())
+    }
+    
+    /// Loads an OBJ file together with its MTL libraries, split into one submesh per material.
+    /// Missing or malformed material libraries and missing textures fall back to
+    /// the defaults and are returned as warnings.
+    pub fn from_model_with_materials(path: &Path) -> Result<LoadedModel, RendererError> {
+        let data = obj::load(path)?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        
+        let mut materials: HashMap<String, Material> = HashMap::new();
+        let mut textures: HashMap<(PathBuf, ColorSpace), Rc<Texture>> = HashMap::new();
+        let mut warnings = Vec::new();
+        for lib in &data.material_libs {
+            for lib_path in resolve_material_lib(base_dir, lib) {
+                if !lib_path.exists() {
+                    warnings.push(RendererError::asset(path, None, format!("material library '{}' not found", lib_path.display())));
+                    continue;
+                }
+                let mtls = match mtl::load(&lib_path) {
+                    Ok(mtls) => mtls,
+                    Err(e) => {
+                        warnings.push(e);
+                        continue;
+                    }
+                };
+                for mtl in mtls {
+                    let material = Material::from_mtl(&mtl, &mut textures, &mut warnings);
+                    materials.insert(mtl.name, material);
+                }
+            }
+        }
+        
+        let mut submeshes = Vec::with_capacity(data.submeshes.len());
+        for submesh in data.submeshes {
+            let material = match &submesh.material {
+                Some(name) => materials.get(name).cloned().unwrap_or_else(|| {
+                    warnings.push(RendererError::asset(path, None, format!("material '{}' is not defined", name)));
+                    Material::default()
+                }),
+                None => Material::default(),
+            };
+            let mut data = MeshData::with_topology(submesh.vertices, submesh.indices, submesh.topology);
+            let triangles = data.topology.is_triangles();
+            if triangles && !data.has_normals() {
+                data.compute_smooth_normals(DEFAULT_CREASE_ANGLE);
+            }
+            // only normal mapped materials need tangents
+            let mesh = if triangles && material.bump_map.is_some() {
+                data.upload_with_tangents()?
+            } else {
+                data.upload()
+            };
+            submeshes.push(SubMesh { mesh, material });
+        }
+        
+        Ok(LoadedModel { submeshes, warnings })
     }
     
+    /// The attributes the vertex buffer provides.
+    pub fn layout(&self) -> &'static [VertexAttribute] {
+        self.layout
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
     pub fn render(&self) {
         self.vao.bind();
-        self.ebo.bind();
+        self.topology.set_patch_vertices();
         unsafe {
-            gl::DrawElements(gl::TRIANGLES, self.indices_len as i32, gl::UNSIGNED_INT, null());
+            match &self.ebo {
+                Some(_) => gl::DrawElements(self.topology.gl_mode(), self.count() as i32, gl::UNSIGNED_INT, null()),
+                None => gl::DrawArrays(self.topology.gl_mode(), 0, self.count() as i32),
+            }
         }
     }
+
+    /// Draws the mesh once per instance in `instances` with a single draw call.
+    pub fn render_instanced<I: VertexLayout>(&self, instances: &InstanceBuffer<I>) {
+        if instances.is_empty() {
+            return;
+        }
+        self.vao.set_vertex_buffer(INSTANCE_BINDING, instances.id(), I::ATTRIBUTES, size_of::<I>(), 1);
+        self.vao.bind();
+        self.topology.set_patch_vertices();
+        unsafe {
+            match &self.ebo {
+                Some(_) => gl::DrawElementsInstanced(self.topology.gl_mode(), self.count() as i32, gl::UNSIGNED_INT, null(), instances.len() as i32),
+                None => gl::DrawArraysInstanced(self.topology.gl_mode(), 0, self.count() as i32, instances.len() as i32),
+            }
+        }
+        // the VAO may be shared with non-instanced draws
+        self.vao.disable_attribs(I::ATTRIBUTES);
+    }
     
     pub fn quad() -> Self {
-        let vertices: Vec<Vertex> = vec![
-            Vertex { v: Vec3::new(-0.5, -0.5, 0.0), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5, -0.5, 0.0), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5, 0.0), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new(-0.5,  0.5, 0.0), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(0.0, 1.0) },
-        ];
-        
-        let indices: Vec<u32> = vec![
-            0, 1, 2,
-            2, 3, 0,
-        ];
-        
-        Mesh::new(&vertices, &indices)
+        MeshData::quad().upload()
     }
     
     pub fn cube() -> Self {
-        // let vertices: Vec<Vertex> = vec![
-        //     // Front face corners
-        //     Vertex { v: Vec3::new(-0.5, -0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(0.0, 0.0)},
-        //     Vertex { v: Vec3::new( 0.5, -0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(1.0, 0.0)},
-        //     Vertex { v: Vec3::new( 0.5,  0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(1.0, 1.0)},
-        //     Vertex { v: Vec3::new(-0.5,  0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(0.0, 1.0)},
-        //     // Back face corners
-        //     Vertex { v: Vec3::new(-0.5, -0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.), vt: Vec2::new(1.0, 0.0)},
-        //     Vertex { v: Vec3::new( 0.5, -0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.), vt: Vec2::new(0.0, 0.0)},
-        //     Vertex { v: Vec3::new( 0.5,  0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.), vt: Vec2::new(0.0, 1.0)},
-        //     Vertex { v: Vec3::new(-0.5,  0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.), vt: Vec2::new(1.0, 1.0)},
-        // ];
-        //
-        // let indices: Vec<u32> = vec![
-        //     // Front
-        //     0, 1, 2,  2, 3, 0,
-        //     // Back
-        //     5, 4, 7,  7, 6, 5,
-        //     // Left
-        //     4, 0, 3,  3, 7, 4,
-        //     // Right
-        //     1, 5, 6,  6, 2, 1,
-        //     // Top
-        //     3, 2, 6,  6, 7, 3,
-        //     // Bottom
-        //     4, 5, 1,  1, 0, 4,
-        // ];
-        
-        let vertices: Vec<Vertex> = vec![
-            // Front face (+Z) - Red
-            Vertex { v: Vec3::new(-0.5, -0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5, -0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new(-0.5,  0.5,  0.5), vn: Vec3::new(0.0, 0.0, 1.0), vt: Vec2::new(0.0, 1.0) },
-            
-            // Back face (-Z) - Green
-            Vertex { v: Vec3::new( 0.5, -0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new(-0.5, -0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new(-0.5,  0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5, -0.5), vn: Vec3::new(0.0, 0.0, -1.0), vt: Vec2::new(0.0, 1.0) },
-            
-            // Top face (+Y) - Blue
-            Vertex { v: Vec3::new(-0.5,  0.5,  0.5), vn: Vec3::new(0.0, 1.0, 0.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5,  0.5), vn: Vec3::new(0.0, 1.0, 0.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5, -0.5), vn: Vec3::new(0.0, 1.0, 0.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new(-0.5,  0.5, -0.5), vn: Vec3::new(0.0, 1.0, 0.0), vt: Vec2::new(0.0, 1.0) },
-            
-            // Bottom face (-Y) - Yellow
-            Vertex { v: Vec3::new(-0.5, -0.5, -0.5), vn: Vec3::new(0.0, -1.0, 0.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5, -0.5, -0.5), vn: Vec3::new(0.0, -1.0, 0.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5, -0.5,  0.5), vn: Vec3::new(0.0, -1.0, 0.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new(-0.5, -0.5,  0.5), vn: Vec3::new(0.0, -1.0, 0.0), vt: Vec2::new(0.0, 1.0) },
-            
-            // Right face (+X) - Magenta
-            Vertex { v: Vec3::new( 0.5, -0.5,  0.5), vn: Vec3::new(1.0, 0.0, 0.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5, -0.5, -0.5), vn: Vec3::new(1.0, 0.0, 0.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5, -0.5), vn: Vec3::new(1.0, 0.0, 0.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new( 0.5,  0.5,  0.5), vn: Vec3::new(1.0, 0.0, 0.0), vt: Vec2::new(0.0, 1.0) },
-            
-            // Left face (-X) - Cyan
-            Vertex { v: Vec3::new(-0.5, -0.5, -0.5), vn: Vec3::new(-1.0, 0.0, 0.0), vt: Vec2::new(0.0, 0.0) },
-            Vertex { v: Vec3::new(-0.5, -0.5,  0.5), vn: Vec3::new(-1.0, 0.0, 0.0), vt: Vec2::new(1.0, 0.0) },
-            Vertex { v: Vec3::new(-0.5,  0.5,  0.5), vn: Vec3::new(-1.0, 0.0, 0.0), vt: Vec2::new(1.0, 1.0) },
-            Vertex { v: Vec3::new(-0.5,  0.5, -0.5), vn: Vec3::new(-1.0, 0.0, 0.0), vt: Vec2::new(0.0, 1.0) },
-        ];
-        
-        let indices: Vec<u32> = vec![
-            // Front
-            0, 1, 2,  2, 3, 0,
-            // Back
-            4, 5, 6,  6, 7, 4,
-            // Top
-            8, 9, 10,  10, 11, 8,
-            // Bottom
-            12, 13, 14,  14, 15, 12,
-            // Right
-            16, 17, 18,  18, 19, 16,
-            // Left
-            20, 21, 22,  22, 23, 20,
-        ];
-        
-        Mesh::new(&vertices, &indices)
+        MeshData::cube().upload()
     }
 }
 
-fn load_from_obj(path: &PathBuf) -> Result<(Vec<Vertex>, Vec<u32>), String> {
-    todo!()
-}
\ No newline at end of file
+/// `mtllib` may list several files, but file names may also contain spaces.
+fn resolve_material_lib(base_dir: &Path, lib: &str) -> Vec<PathBuf> {
+    let whole = base_dir.join(lib);
+    if whole.exists() {
+        return vec![whole];
+    }
+    lib.split_whitespace().map(|name| base_dir.join(name)).collect()
+}
@@ -0,0 +1,693 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use glm::{GenMat, GenSquareMat, Mat3, Mat4, Vec3, Vec4};
+use num_traits::Zero;
+use crate::renderer::buffer::BufferUsage;
+use crate::renderer::error::RendererError;
+use crate::renderer::mesh::{Mesh, Topology};
+use crate::renderer::obj;
+use crate::renderer::vertex::{TangentVertex, Vertex};
+use crate::renderer::vertex_layout::VertexLayout;
+
+/// Crease angle used when loaders have to generate missing normals.
+pub const DEFAULT_CREASE_ANGLE: f32 = 60.0 * std::f32::consts::PI / 180.0;
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub fn from_points<'a>(points: impl Iterator<Item = &'a Vec3>) -> Self {
+        let mut min = Vec3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Vec3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        let mut any = false;
+        for p in points {
+            min = glm::min(min, *p);
+            max = glm::max(max, *p);
+            any = true;
+        }
+        if !any {
+            return Self { min: Vec3::zero(), max: Vec3::zero() };
+        }
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
+
+/// CPU side geometry. Can be inspected and modified without a GL context and
+/// is turned into a `Mesh` with `upload`.
+#[derive(Clone, Debug)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    /// Empty for non-indexed meshes, which use the vertices in order.
+    pub indices: Vec<u32>,
+    pub topology: Topology,
+
+    bounds: Bounds,
+}
+
+impl MeshData {
+    /// An indexed triangle list.
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self::with_topology(vertices, indices, Topology::Triangles)
+    }
+
+    pub fn with_topology(vertices: Vec<Vertex>, indices: Vec<u32>, topology: Topology) -> Self {
+        let bounds = Bounds::from_points(vertices.iter().map(|v| &v.v));
+        Self { vertices, indices, topology, bounds }
+    }
+
+    /// Vertices used in order, drawn with `DrawArrays`.
+    pub fn non_indexed(vertices: Vec<Vertex>, topology: Topology) -> Self {
+        Self::with_topology(vertices, Vec::new(), topology)
+    }
+
+    /// Loads all geometry of an OBJ file. Missing normals are generated.
+    pub fn from_obj(path: &Path) -> Result<Self, RendererError> {
+        let (vertices, indices) = obj::load(path)?.merged();
+        let mut data = Self::new(vertices, indices);
+        if !data.has_normals() {
+            data.compute_smooth_normals(DEFAULT_CREASE_ANGLE);
+        }
+        Ok(data)
+    }
+
+    /// Creates the GPU mesh. The data stays untouched and can be uploaded again.
+    pub fn upload(&self) -> Mesh {
+        Mesh::new(self)
+    }
+
+    /// Creates the GPU mesh with `TangentVertex` vertices, generating the
+    /// tangents with `compute_tangents`.
+    pub fn upload_with_tangents(&mut self) -> Result<Mesh, RendererError> {
+        let vertices = self.compute_tangents()?;
+        Ok(Mesh::from_vertices(&vertices, &self.indices, self.topology, BufferUsage::Static))
+    }
+
+    /// Creates the GPU mesh with another vertex layout, e.g. `PositionVertex` for shadow passes.
+    pub fn upload_as<V: VertexLayout + From<Vertex>>(&self) -> Mesh {
+        let vertices: Vec<V> = self.vertices.iter().map(|&vertex| V::from(vertex)).collect();
+        Mesh::from_vertices(&vertices, &self.indices, self.topology, BufferUsage::Static)
+    }
+
+    /// Bounds as of the last `recompute_bounds`. Operations of `MeshData`
+    /// keep them up to date, direct edits of `vertices` don't.
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn recompute_bounds(&mut self) {
+        self.bounds = Bounds::from_points(self.vertices.iter().map(|v| &v.v));
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        !self.indices.is_empty()
+    }
+
+    /// Number of indices, or of vertices for non-indexed meshes.
+    pub fn element_count(&self) -> usize {
+        if self.is_indexed() { self.indices.len() } else { self.vertices.len() }
+    }
+
+    /// Triangles drawn, zero for point and line meshes.
+    pub fn triangle_count(&self) -> usize {
+        let count = self.element_count();
+        match self.topology {
+            Topology::Triangles => count / 3,
+            Topology::TriangleStrip | Topology::TriangleFan => count.saturating_sub(2),
+            _ => 0,
+        }
+    }
+
+    /// Indexes a non-indexed mesh with `0..vertices.len()`.
+    pub fn make_indexed(&mut self) {
+        if !self.is_indexed() {
+            self.indices = (0..self.vertices.len() as u32).collect();
+        }
+    }
+
+    /// Unrolls strips, loops and fans into plain point, line or triangle lists,
+    /// which the editing operations below work on. The result is always indexed.
+    pub fn unroll(&mut self) {
+        self.make_indexed();
+        let i = &self.indices;
+        let indices: Vec<u32> = match self.topology {
+            Topology::Points | Topology::Lines | Topology::Triangles | Topology::Patches(_) => return,
+            Topology::LineStrip => i.windows(2).flatten().copied().collect(),
+            Topology::LineLoop => {
+                let mut lines: Vec<u32> = i.windows(2).flatten().copied().collect();
+                if i.len() > 2 {
+                    lines.extend([i[i.len() - 1], i[0]]);
+                }
+                lines
+            }
+            Topology::TriangleStrip => i.windows(3).enumerate()
+                // every second triangle of a strip is wound the other way
+                .flat_map(|(n, t)| if n % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+                .collect(),
+            Topology::TriangleFan => (1..i.len().saturating_sub(1))
+                .flat_map(|n| [i[0], i[n], i[n + 1]])
+                .collect(),
+        };
+        self.indices = indices;
+        self.topology = self.topology.list();
+    }
+
+    /// Edges of all triangles as a line list, each edge once. Useful for wireframe overlays.
+    pub fn wireframe(&self) -> MeshData {
+        let mut data = self.clone();
+        data.unroll();
+        if data.topology != Topology::Triangles {
+            return data;
+        }
+
+        let mut seen = HashSet::new();
+        let mut lines = Vec::new();
+        for t in data.indices.chunks_exact(3) {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                let (pa, pb) = (position_key(data.vertices[a as usize].v), position_key(data.vertices[b as usize].v));
+                // edges are shared by position, split vertices would draw them twice
+                if seen.insert(if pa <= pb { (pa, pb) } else { (pb, pa) }) {
+                    lines.extend([a, b]);
+                }
+            }
+        }
+        data.indices = lines;
+        data.topology = Topology::Lines;
+        data
+    }
+
+    /// Transforms positions by `matrix` and normals by its inverse transpose.
+    /// Mirroring transforms flip the winding so faces stay front facing.
+    pub fn transform(&mut self, matrix: &Mat4) {
+        let upper = Mat3::new(matrix.c0.truncate(3), matrix.c1.truncate(3), matrix.c2.truncate(3));
+        let normal_matrix = upper.inverse().map(|inv| inv.transpose()).unwrap_or(upper);
+
+        for vertex in self.vertices.iter_mut() {
+            let p = *matrix * Vec4::new(vertex.v.x, vertex.v.y, vertex.v.z, 1.0);
+            vertex.v = Vec3::new(p.x, p.y, p.z) / p.w;
+
+            let n = normal_matrix * vertex.vn;
+            if glm::length(n) > 0.0 {
+                vertex.vn = glm::normalize(n);
+            }
+        }
+
+        if upper.determinant() < 0.0 && self.topology.is_triangles() {
+            self.flip_winding();
+        }
+        self.recompute_bounds();
+    }
+
+    /// Appends the geometry of `other`, offsetting its indices. Both meshes are
+    /// turned into lists first and have to be of the same kind, otherwise
+    /// neither is changed.
+    pub fn merge(&mut self, other: &MeshData) -> Result<(), RendererError> {
+        if self.topology.list() != other.topology.list() {
+            return Err(RendererError::Invalid(format!("Can't merge a {:?} mesh into a {:?} mesh", other.topology, self.topology)));
+        }
+        let mut other = other.clone();
+        other.unroll();
+        self.unroll();
+
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices.extend(other.indices.iter().map(|i| i + offset));
+        self.recompute_bounds();
+        Ok(())
+    }
+
+    /// Reverses the order of every triangle, strips and fans become lists.
+    /// Normals are left alone, see `flip_normals`.
+    pub fn flip_winding(&mut self) {
+        if !self.topology.is_triangles() {
+            return;
+        }
+        self.unroll();
+        for triangle in self.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    pub fn flip_normals(&mut self) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.vn = -vertex.vn;
+        }
+    }
+
+    /// Checks that the elements form whole primitives and indices reference existing vertices.
+    pub fn validate(&self) -> Result<(), RendererError> {
+        let count = self.element_count();
+        let what = if self.is_indexed() { "Index" } else { "Vertex" };
+        match self.topology {
+            Topology::Triangles if !count.is_multiple_of(3) => {
+                return Err(RendererError::Invalid(format!("{} count {} is not a multiple of 3", what, count)));
+            }
+            Topology::Lines if !count.is_multiple_of(2) => {
+                return Err(RendererError::Invalid(format!("{} count {} is not a multiple of 2", what, count)));
+            }
+            Topology::TriangleStrip | Topology::TriangleFan if count == 1 || count == 2 => {
+                return Err(RendererError::Invalid(format!("{} count {} is too small for a {:?}", what, count, self.topology)));
+            }
+            Topology::LineStrip | Topology::LineLoop if count == 1 => {
+                return Err(RendererError::Invalid(format!("{} count {} is too small for a {:?}", what, count, self.topology)));
+            }
+            Topology::Patches(0) => {
+                return Err(RendererError::Invalid(String::from("Patches need at least one vertex")));
+            }
+            Topology::Patches(size) if !count.is_multiple_of(size as usize) => {
+                return Err(RendererError::Invalid(format!("{} count {} is not a multiple of the patch size {}", what, count, size)));
+            }
+            _ => {}
+        }
+        if let Some((position, index)) = self.indices.iter().enumerate().find(|(_, i)| **i as usize >= self.vertices.len()) {
+            return Err(RendererError::Invalid(format!("Index {} at position {} out of range, mesh has {} vertices", index, position, self.vertices.len())));
+        }
+        Ok(())
+    }
+
+    /// True when every vertex carries a non-zero normal.
+    pub fn has_normals(&self) -> bool {
+        self.vertices.iter().all(|v| glm::length(v.vn) > 0.0)
+    }
+
+    /// Gives every triangle its own vertices with the face normal. Vertices
+    /// that end up identical are welded again. Does nothing for points and lines.
+    pub fn compute_flat_normals(&mut self) {
+        if !self.topology.is_triangles() {
+            return;
+        }
+        self.unroll();
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]].map(|i| self.vertices[i as usize]);
+            let normal = face_normal(corners[0].v, corners[1].v, corners[2].v);
+            for mut corner in corners {
+                corner.vn = normal;
+                vertices.push(corner);
+            }
+        }
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+        self.weld();
+    }
+
+    /// Averages the normals of all faces sharing a position, weighted by the
+    /// angle of each face at that corner. Faces whose normals differ by more
+    /// than `crease_angle` (radians) don't influence each other, which keeps
+    /// hard edges sharp. Vertices are split where that's needed. Does nothing
+    /// for points and lines.
+    pub fn compute_smooth_normals(&mut self, crease_angle: f32) {
+        if !self.topology.is_triangles() {
+            return;
+        }
+        self.unroll();
+        let cos_crease = crease_angle.cos();
+        let triangles: Vec<[usize; 3]> = self.indices.chunks_exact(3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+            .collect();
+
+        let face_normals: Vec<Vec3> = triangles.iter()
+            .map(|t| face_normal(self.vertices[t[0]].v, self.vertices[t[1]].v, self.vertices[t[2]].v))
+            .collect();
+
+        // corners grouped by position, so seams in uvs or normals still get smoothed
+        let mut shared: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+        for (face, t) in triangles.iter().enumerate() {
+            for corner in 0..3 {
+                let angle = corner_angle(&self.vertices, t, corner);
+                shared.entry(position_key(self.vertices[t[corner]].v)).or_default().push((face, angle));
+            }
+        }
+
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for (face, t) in triangles.iter().enumerate() {
+            for &index in t {
+                let mut vertex = self.vertices[index];
+                let mut normal = Vec3::zero();
+                for &(other, angle) in &shared[&position_key(vertex.v)] {
+                    if glm::dot(face_normals[face], face_normals[other]) >= cos_crease {
+                        normal = normal + face_normals[other] * angle;
+                    }
+                }
+                vertex.vn = if glm::length(normal) > 0.0 { glm::normalize(normal) } else { face_normals[face] };
+                vertices.push(vertex);
+            }
+        }
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+        self.weld();
+    }
+
+    /// Generates MikkTSpace tangents, the tangent space normal map bakers use,
+    /// and returns the vertices with them, in the order of `vertices`. `w`
+    /// holds the bitangent sign so `bitangent = cross(normal, tangent.xyz) * tangent.w`.
+    /// Vertices whose corners get different tangents, e.g. on mirrored uvs, are
+    /// split, so `indices` may change. Normals have to be present. Points and
+    /// lines get zero tangents.
+    pub fn compute_tangents(&mut self) -> Result<Vec<TangentVertex>, RendererError> {
+        self.validate()?;
+        if !self.topology.is_triangles() {
+            return Ok(self.vertices.iter().map(|&vertex| TangentVertex::new(vertex, Vec4::zero())).collect());
+        }
+        self.unroll();
+
+        let mut geometry = TangentGeometry { data: self, tangents: vec![Vec4::zero(); self.indices.len()] };
+        if !bevy_mikktspace::generate_tangents(&mut geometry) {
+            return Err(RendererError::Invalid(String::from("Could not generate tangents")));
+        }
+        let corner_tangents = geometry.tangents;
+
+        // the generator works per corner, corners of one vertex that got
+        // different tangents are moved to copies of it
+        let mut tangents: Vec<Option<Vec4>> = vec![None; self.vertices.len()];
+        let mut copies: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+        for (index, tangent) in self.indices.iter_mut().zip(corner_tangents) {
+            let i = *index as usize;
+            match tangents[i] {
+                None => tangents[i] = Some(tangent),
+                Some(known) if known == tangent => {}
+                Some(_) => {
+                    let key = [tangent.x, tangent.y, tangent.z, tangent.w].map(|f| (f + 0.0).to_bits());
+                    *index = *copies.entry((*index, key)).or_insert_with(|| {
+                        self.vertices.push(self.vertices[i]);
+                        tangents.push(Some(tangent));
+                        (self.vertices.len() - 1) as u32
+                    });
+                }
+            }
+        }
+
+        Ok(self.vertices.iter().zip(tangents)
+            .map(|(&vertex, tangent)| TangentVertex::new(vertex, tangent.unwrap_or(Vec4::zero())))
+            .collect())
+    }
+
+    /// Merges vertices that are bit for bit identical.
+    pub fn weld(&mut self) {
+        self.make_indexed();
+        let mut lookup: HashMap<[u32; 8], u32> = HashMap::new();
+        let mut vertices = Vec::new();
+        for index in self.indices.iter_mut() {
+            let vertex = self.vertices[*index as usize];
+            *index = *lookup.entry(vertex_key(&vertex)).or_insert_with(|| {
+                vertices.push(vertex);
+                (vertices.len() - 1) as u32
+            });
+        }
+        self.vertices = vertices;
+    }
+}
+
+/// A triangle list `MeshData` as seen by the MikkTSpace generator, which
+/// hands out one tangent per corner.
+struct TangentGeometry<'a> {
+    data: &'a MeshData,
+    tangents: Vec<Vec4>,
+}
+
+impl TangentGeometry<'_> {
+    fn corner(&self, face: usize, vert: usize) -> &Vertex {
+        &self.data.vertices[self.data.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.data.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let v = self.corner(face, vert).v;
+        [v.x, v.y, v.z]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let n = self.corner(face, vert).vn;
+        [n.x, n.y, n.z]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let vt = self.corner(face, vert).vt;
+        [vt.x, vt.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Vec4::new(tangent[0], tangent[1], tangent[2], tangent[3]);
+    }
+}
+
+fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let n = glm::cross(b - a, c - a);
+    if glm::length(n) > 0.0 { glm::normalize(n) } else { Vec3::zero() }
+}
+
+/// Angle of triangle `t` at `corner`.
+fn corner_angle(vertices: &[Vertex], t: &[usize; 3], corner: usize) -> f32 {
+    let p = vertices[t[corner]].v;
+    let e1 = vertices[t[(corner + 1) % 3]].v - p;
+    let e2 = vertices[t[(corner + 2) % 3]].v - p;
+    if glm::length(e1) == 0.0 || glm::length(e2) == 0.0 {
+        return 0.0;
+    }
+    glm::dot(glm::normalize(e1), glm::normalize(e2)).clamp(-1.0, 1.0).acos()
+}
+
+// adding 0.0 turns -0.0 into 0.0 so both hash the same
+fn position_key(p: Vec3) -> [u32; 3] {
+    [p.x, p.y, p.z].map(|f| (f + 0.0).to_bits())
+}
+
+fn vertex_key(v: &Vertex) -> [u32; 8] {
+    [
+        v.v.x, v.v.y, v.v.z,
+        v.vn.x, v.vn.y, v.vn.z,
+        v.vt.x, v.vt.y,
+    ].map(|f| (f + 0.0).to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::Vec2;
+    use num_traits::One;
+    use super::*;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex::new(Vec3::new(x, y, z), Vec3::new(0.0, 0.0, 1.0), Vec2::new(x, y))
+    }
+
+    /// Two triangles forming the unit square in the xy plane, facing +z.
+    fn square() -> MeshData {
+        MeshData::new(
+            vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(1.0, 1.0, 0.0), vertex(0.0, 1.0, 0.0)],
+            vec![0, 1, 2, 0, 2, 3],
+        )
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(glm::length(a - b) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn transform_moves_positions_and_bounds() {
+        let mut data = square();
+        let matrix = glm::ext::scale(&glm::ext::translate(&Mat4::one(), Vec3::new(1.0, 2.0, 3.0)), Vec3::new(2.0, 2.0, 2.0));
+        data.transform(&matrix);
+
+        assert_close(data.vertices[2].v, Vec3::new(3.0, 4.0, 3.0));
+        assert_close(data.vertices[2].vn, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(data.bounds(), Bounds { min: Vec3::new(1.0, 2.0, 3.0), max: Vec3::new(3.0, 4.0, 3.0) });
+        assert_eq!(data.indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn transform_keeps_normals_perpendicular() {
+        // a slanted face, stretched along x
+        let n = glm::normalize(Vec3::new(1.0, 0.0, 1.0));
+        let mut data = MeshData::new(vec![
+            Vertex::new(Vec3::new(0.0, 0.0, 0.0), n, Vec2::zero()),
+            Vertex::new(Vec3::new(0.0, 1.0, 0.0), n, Vec2::zero()),
+            Vertex::new(Vec3::new(1.0, 0.0, -1.0), n, Vec2::zero()),
+        ], vec![0, 1, 2]);
+        data.transform(&glm::ext::scale(&Mat4::one(), Vec3::new(4.0, 1.0, 1.0)));
+
+        let edge = data.vertices[2].v - data.vertices[0].v;
+        assert!(glm::dot(edge, data.vertices[0].vn).abs() < 1e-5);
+        assert!((glm::length(data.vertices[0].vn) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn mirroring_transform_flips_winding() {
+        let mut data = square();
+        data.transform(&glm::ext::scale(&Mat4::one(), Vec3::new(-1.0, 1.0, 1.0)));
+        assert_eq!(data.indices, [0, 2, 1, 0, 3, 2]);
+        assert_close(data.vertices[1].v, Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn merge_offsets_indices() {
+        let mut data = square();
+        let mut other = square();
+        other.transform(&glm::ext::translate(&Mat4::one(), Vec3::new(0.0, 0.0, -2.0)));
+        data.merge(&other).unwrap();
+
+        assert_eq!(data.vertices.len(), 8);
+        assert_eq!(data.indices, [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+        assert_eq!(data.bounds(), Bounds { min: Vec3::new(0.0, 0.0, -2.0), max: Vec3::new(1.0, 1.0, 0.0) });
+    }
+
+    #[test]
+    fn merge_unrolls_strips() {
+        let mut data = square();
+        let strip = MeshData::non_indexed(vec![vertex(0.0, 0.0, 1.0), vertex(1.0, 0.0, 1.0), vertex(0.0, 1.0, 1.0), vertex(1.0, 1.0, 1.0)], Topology::TriangleStrip);
+        data.merge(&strip).unwrap();
+
+        assert_eq!(data.topology, Topology::Triangles);
+        assert_eq!(data.indices[6..], [4, 5, 6, 6, 5, 7]);
+    }
+
+    #[test]
+    fn merge_rejects_other_topologies() {
+        let mut data = square();
+        let lines = MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 1.0, 1.0)], Topology::LineStrip);
+        assert!(data.merge(&lines).is_err());
+        assert_eq!(data.vertices.len(), 4);
+        assert_eq!(data.bounds().max, Vec3::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn flip_winding_reverses_triangles() {
+        let mut data = square();
+        data.flip_winding();
+        assert_eq!(data.indices, [0, 2, 1, 0, 3, 2]);
+        data.flip_winding();
+        assert_eq!(data.indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn flip_winding_unrolls_fans_and_ignores_lines() {
+        let mut fan = MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(1.0, 1.0, 0.0), vertex(0.0, 1.0, 0.0)], Topology::TriangleFan);
+        fan.flip_winding();
+        assert_eq!(fan.topology, Topology::Triangles);
+        assert_eq!(fan.indices, [0, 2, 1, 0, 3, 2]);
+
+        let mut lines = MeshData::with_topology(vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0)], vec![0, 1], Topology::Lines);
+        lines.flip_winding();
+        assert_eq!(lines.indices, [0, 1]);
+    }
+
+    #[test]
+    fn recompute_bounds_picks_up_direct_edits() {
+        let mut data = square();
+        data.vertices[2].v = Vec3::new(5.0, -1.0, 2.0);
+        assert_eq!(data.bounds().max, Vec3::new(1.0, 1.0, 0.0));
+
+        data.recompute_bounds();
+        assert_eq!(data.bounds(), Bounds { min: Vec3::new(0.0, -1.0, 0.0), max: Vec3::new(5.0, 1.0, 2.0) });
+
+        data.vertices.clear();
+        data.recompute_bounds();
+        assert_eq!(data.bounds(), Bounds { min: Vec3::zero(), max: Vec3::zero() });
+    }
+
+    #[test]
+    fn tangents_follow_uvs() {
+        let mut data = square();
+        let vertices = data.compute_tangents().unwrap();
+        assert_eq!(vertices.len(), 4);
+        for vertex in vertices {
+            assert_eq!(vertex.tangent, Vec4::new(1.0, 0.0, 0.0, 1.0));
+            assert_close(vertex.bitangent(), Vec3::new(0.0, 1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn tangents_split_mirrored_uvs() {
+        // the right half mirrors the uvs of the left half around x = 1
+        let mut data = MeshData::new(vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(1.0, 1.0, 0.0), vertex(0.0, 1.0, 0.0),
+            Vertex::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new(2.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 1.0)),
+        ], vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2]);
+        let vertices = data.compute_tangents().unwrap();
+
+        // the shared edge is split so each side keeps its handedness
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(data.vertices.len(), 8);
+        for triangle in data.indices.chunks_exact(3) {
+            let w = if triangle.contains(&4) || triangle.contains(&5) { -1.0 } else { 1.0 };
+            for &i in triangle {
+                assert_eq!(vertices[i as usize].tangent.w, w);
+                assert_close(vertices[i as usize].bitangent(), Vec3::new(0.0, 1.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_are_unit_and_perpendicular_to_normals() {
+        let mut data = MeshData::torus(1.0, 0.25, 16, 8);
+        let vertices = data.compute_tangents().unwrap();
+        assert_eq!(vertices.len(), data.vertices.len());
+        for vertex in vertices {
+            let tangent = Vec3::new(vertex.tangent.x, vertex.tangent.y, vertex.tangent.z);
+            assert!((glm::length(tangent) - 1.0).abs() < 1e-4, "{:?}", tangent);
+            assert!(glm::dot(tangent, vertex.vn).abs() < 1e-4, "{:?} against {:?}", tangent, vertex.vn);
+            assert_eq!(vertex.tangent.w.abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn tangents_require_a_valid_mesh() {
+        let mut partial = square();
+        partial.indices.pop();
+        assert!(partial.compute_tangents().is_err());
+
+        let mut out_of_range = square();
+        out_of_range.indices[4] = 9;
+        assert!(out_of_range.compute_tangents().is_err());
+    }
+
+    #[test]
+    fn validate_accepts_whole_primitives() {
+        assert!(square().validate().is_ok());
+        assert!(MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0); 3], Topology::TriangleStrip).validate().is_ok());
+        assert!(MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0); 8], Topology::Patches(4)).validate().is_ok());
+        assert!(MeshData::non_indexed(Vec::new(), Topology::Triangles).validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_broken_meshes() {
+        let mut partial = square();
+        partial.indices.pop();
+        let mut out_of_range = square();
+        out_of_range.indices[4] = 4;
+
+        let cases = [
+            (partial, "Index count 5 is not a multiple of 3"),
+            (out_of_range, "Index 4 at position 4 out of range"),
+            (MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0); 3], Topology::Lines), "Vertex count 3 is not a multiple of 2"),
+            (MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0); 2], Topology::TriangleFan), "too small for a TriangleFan"),
+            (MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0)], Topology::LineLoop), "too small for a LineLoop"),
+            (MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0); 3], Topology::Patches(0)), "at least one vertex"),
+            (MeshData::non_indexed(vec![vertex(0.0, 0.0, 0.0); 5], Topology::Patches(4)), "patch size 4"),
+        ];
+        for (data, message) in cases {
+            let error = data.validate().expect_err(message).to_string();
+            assert!(error.contains(message), "'{}' should contain '{}'", error, message);
+        }
+    }
+}
@@ -1,7 +1,28 @@
+pub mod gl_caps;
+pub mod error;
 pub mod buffer;
 pub mod vertex;
+pub mod vertex_layout;
 pub mod shader;
+pub mod preprocessor;
+pub mod shader_variants;
+pub mod shader_watcher;
+pub mod program_cache;
+pub mod spirv;
+pub mod compute;
+pub mod reflection;
 pub mod mesh;
+pub mod mesh_data;
+pub mod streaming_mesh;
+pub mod primitives;
+pub mod obj;
+pub mod mtl;
+pub mod material;
+pub mod scene;
 pub mod model;
+pub mod instancing;
 pub mod camera;
-pub mod texture;
\ No newline at end of file
+pub mod sampler;
+pub mod texture;
+pub mod texture_format;
+pub mod framebuffer;
\ No newline at end of file
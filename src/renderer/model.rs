@@ -2,8 +2,11 @@ use std::cell::{Ref, RefCell, RefMut};
 use std::rc::Rc;
 use glm::{Mat4, Vec3, Vec4};
 use num_traits::{One, Zero};
-use crate::renderer::mesh::Mesh;
+use crate::renderer::error::RendererError;
+use crate::renderer::material::Material;
+use crate::renderer::mesh::{Mesh, SubMesh};
 use crate::renderer::shader::Shader;
+use crate::renderer::shader_variants::ShaderVariants;
 
 pub struct Transform {
     position: Vec3,
@@ -71,6 +74,7 @@ pub struct Model {
     
     transform: RefCell<Transform>,
     tint: Vec4,
+    material: Material,
 }
 
 impl Model {
@@ -79,29 +83,36 @@ impl Model {
             mesh,
             shader,
             transform: RefCell::new(Transform::new(Vec3::zero(), Vec3::one(), Vec3::zero())),
-            tint: Vec4::one()
+            tint: Vec4::one(),
+            material: Material::default(),
         }
     }
     
     pub fn with_transform(mesh: Rc<RefCell<Mesh>>, shader: Rc<RefCell<Shader>>, transform: Transform) -> Self {
-        Self { mesh, shader, transform: RefCell::new(transform), tint: Vec4::one() }
+        Self { mesh, shader, transform: RefCell::new(transform), tint: Vec4::one(), material: Material::default() }
     }
     
-    pub fn render(&self, pv_mat: Mat4) {
-        self.shader.borrow().bind();
-        
-        let pvm_loc = self.shader_mut().get_uniform_location("pvm").unwrap();
-        self.shader().set_uniform(pvm_loc, pv_mat * self.transform().model_matrix());
-        
+    pub fn with_material(mesh: Rc<RefCell<Mesh>>, shader: Rc<RefCell<Shader>>, transform: Transform, material: Material) -> Self {
+        Self { mesh, shader, transform: RefCell::new(transform), tint: Vec4::one(), material }
+    }
+    
+    pub fn from_submesh(submesh: SubMesh, shader: Rc<RefCell<Shader>>, transform: Transform) -> Self {
+        Self::with_material(Rc::new(RefCell::new(submesh.mesh)), shader, transform, submesh.material)
+    }
+    
+    /// Draws the model, fails without drawing if a uniform has a different type in the shader.
+    pub fn render(&self, pv_mat: Mat4) -> Result<(), RendererError> {
         {
             let mut shader = self.shader.borrow_mut();
-            if let Some(tint_loc) = shader.get_uniform_location("tint") {
-                shader.set_uniform(tint_loc, self.tint);
-            }
+            shader.bind();
+            shader.set("pvm", pv_mat * self.transform().model_matrix())?;
+            shader.set("tint", self.tint)?;
+            self.material.apply(&mut shader)?;
         }
         
         
         self.mesh.borrow().render();
+        Ok(())
     }
     
     pub fn transform(&self) -> Ref<'_, Transform> {
@@ -120,6 +131,18 @@ impl Model {
         self.tint = tint
     }
     
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+    
+    pub fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+    
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material
+    }
+    
     pub fn mesh(&self) -> Ref<'_, Mesh> {
         self.mesh.borrow()
     }
@@ -135,4 +158,19 @@ impl Model {
     pub fn shader_mut(&self) -> RefMut<'_, Shader> {
         self.shader.borrow_mut()
     }
+    
+    /// Checks that the mesh supplies every vertex input the shader reads.
+    pub fn check_layout(&self) -> Result<(), RendererError> {
+        self.shader.borrow().check_mesh(&self.mesh.borrow())
+    }
+    
+    pub fn set_shader(&mut self, shader: Rc<RefCell<Shader>>) {
+        self.shader = shader
+    }
+    
+    /// Switches to the variant of `variants` matching the current material.
+    pub fn use_variant(&mut self, variants: &mut ShaderVariants) -> Result<(), RendererError> {
+        self.shader = variants.for_material(&self.material)?;
+        Ok(())
+    }
 }
\ No newline at end of file
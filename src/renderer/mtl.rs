@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+use glm::Vec3;
+use crate::renderer::error::RendererError;
+
+/// A material as described in a Wavefront MTL file. Texture paths are
+/// resolved relative to the MTL file but not loaded.
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub opacity: f32,
+    pub emissive: Vec3,
+    pub diffuse_map: Option<PathBuf>,
+    pub bump_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub emissive_map: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: Vec3::new(1.0, 1.0, 1.0),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 32.0,
+            opacity: 1.0,
+            emissive: Vec3::new(0.0, 0.0, 0.0),
+            diffuse_map: None,
+            bump_map: None,
+            specular_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<MtlMaterial>, RendererError> {
+    let src = std::fs::read_to_string(path).map_err(|e| RendererError::io(path, e))?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    parse(&src, base_dir).map_err(|(line, msg)| RendererError::asset(path, Some(line), msg))
+}
+
+/// Parses MTL source. Texture paths are joined onto `base_dir`.
+/// Errors carry the 1-based line number they occurred on.
+pub fn parse(src: &str, base_dir: &Path) -> Result<Vec<MtlMaterial>, (usize, String)> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (line_idx, line) in src.lines().enumerate() {
+        let line_nr = line_idx + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let args = &line.trim_start()[keyword.len()..];
+
+        if keyword == "newmtl" {
+            let name = args.trim();
+            if name.is_empty() {
+                return Err((line_nr, String::from("newmtl without material name")));
+            }
+            materials.push(MtlMaterial::new(name.to_string()));
+            continue;
+        }
+
+        let Some(material) = materials.last_mut() else {
+            return Err((line_nr, format!("'{keyword}' before any newmtl")));
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&mut tokens).map_err(|e| (line_nr, e))?,
+            "Ke" => material.emissive = parse_color(&mut tokens).map_err(|e| (line_nr, e))?,
+            "Ks" => material.specular = parse_color(&mut tokens).map_err(|e| (line_nr, e))?,
+            "Ns" => material.shininess = parse_float(&mut tokens).map_err(|e| (line_nr, e))?,
+            "d" => material.opacity = parse_float(&mut tokens).map_err(|e| (line_nr, e))?,
+            // transparency, the inverse of d
+            "Tr" => material.opacity = 1.0 - parse_float(&mut tokens).map_err(|e| (line_nr, e))?,
+            "map_Kd" => material.diffuse_map = Some(parse_map(args, base_dir).map_err(|e| (line_nr, e))?),
+            "map_Bump" | "map_bump" | "bump" => material.bump_map = Some(parse_map(args, base_dir).map_err(|e| (line_nr, e))?),
+            "map_Ks" => material.specular_map = Some(parse_map(args, base_dir).map_err(|e| (line_nr, e))?),
+            "map_Ke" => material.emissive_map = Some(parse_map(args, base_dir).map_err(|e| (line_nr, e))?),
+            // Ka, Ni, illum and friends aren't used by the renderer
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn parse_float<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<f32, String> {
+    let token = tokens.next().ok_or(String::from("Missing value"))?;
+    token.parse::<f32>().map_err(|_| format!("Invalid number '{token}'"))
+}
+
+/// Reads an `r g b` triple. A single value is used for all three channels.
+fn parse_color<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec3, String> {
+    let r = parse_float(tokens)?;
+    match tokens.next() {
+        None => Ok(Vec3::new(r, r, r)),
+        Some(token) => {
+            let g = token.parse::<f32>().map_err(|_| format!("Invalid number '{token}'"))?;
+            let b = parse_float(tokens)?;
+            Ok(Vec3::new(r, g, b))
+        }
+    }
+}
+
+/// Texture statements may carry options such as `-bm 1.0` or `-o u v w`
+/// before the file name. Everything after the options is the file name, so
+/// names containing spaces are kept whole.
+fn parse_map(args: &str, base_dir: &Path) -> Result<PathBuf, String> {
+    let mut rest = args.trim();
+    while let Some((option, after)) = split_token(rest) {
+        // (required, optional) numbers of values
+        let (required, optional) = match option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => (1, 0),
+            "-mm" => (2, 0),
+            "-o" | "-s" | "-t" => (1, 2),
+            _ => break,
+        };
+        rest = after;
+        for n in 0..required + optional {
+            match split_token(rest) {
+                Some((value, after)) if n < required || value.parse::<f32>().is_ok() => rest = after,
+                None if n < required => return Err(format!("Missing value for texture option '{option}'")),
+                _ => break,
+            }
+        }
+    }
+    if rest.is_empty() {
+        return Err(String::from("Missing texture file name"));
+    }
+    Ok(base_dir.join(rest))
+}
+
+/// The first whitespace separated token and the trimmed rest.
+fn split_token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+    if s.is_empty() {
+        return None;
+    }
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    Some((&s[..end], s[end..].trim_start()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(src: &str) -> Vec<MtlMaterial> {
+        parse(src, Path::new("models")).unwrap()
+    }
+
+    #[test]
+    fn materials_and_parameters() {
+        let materials = parse_ok("\
+# two materials
+newmtl brushed steel
+Kd 0.5 0.25 1
+Ks 0.8
+Ns 96 # shiny
+d 0.75
+Ke 0 0.5 0
+illum 2
+
+newmtl glass
+Tr 0.9
+");
+        assert_eq!(materials.len(), 2);
+        let steel = &materials[0];
+        assert_eq!(steel.name, "brushed steel");
+        assert_eq!(steel.diffuse, Vec3::new(0.5, 0.25, 1.0));
+        assert_eq!(steel.specular, Vec3::new(0.8, 0.8, 0.8));
+        assert_eq!(steel.shininess, 96.0);
+        assert_eq!(steel.opacity, 0.75);
+        assert_eq!(steel.emissive, Vec3::new(0.0, 0.5, 0.0));
+
+        let glass = &materials[1];
+        assert_eq!(glass.diffuse, Vec3::new(1.0, 1.0, 1.0));
+        assert!((glass.opacity - 0.1).abs() < 1e-6);
+        assert!(glass.diffuse_map.is_none());
+    }
+
+    #[test]
+    fn texture_maps_skip_options() {
+        let materials = parse_ok("\
+newmtl textured
+map_Kd -s 2 2 -o 0.5 albedo.png
+map_Bump -bm 1.0 normal.png
+bump -imfchan r -mm 0 1 height.png
+map_Ks -clamp on -t 0.1 spec.png
+map_Ke -o 1 -blendu off glow.png
+");
+        let textured = &materials[0];
+        assert_eq!(textured.diffuse_map, Some(PathBuf::from("models/albedo.png")));
+        assert_eq!(textured.bump_map, Some(PathBuf::from("models/height.png")));
+        assert_eq!(textured.specular_map, Some(PathBuf::from("models/spec.png")));
+        assert_eq!(textured.emissive_map, Some(PathBuf::from("models/glow.png")));
+    }
+
+    #[test]
+    fn texture_file_names_keep_spaces() {
+        let materials = parse_ok("newmtl spaced\nmap_Kd -bm 1.0 textures/old  brick wall.png  \nmap_Ks -dashed name.png\n");
+        assert_eq!(materials[0].diffuse_map, Some(PathBuf::from("models/textures/old  brick wall.png")));
+        // unknown options start the file name
+        assert_eq!(materials[0].specular_map, Some(PathBuf::from("models/-dashed name.png")));
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let error = |src: &str| parse(src, Path::new("")).err().unwrap();
+        assert_eq!(error("Kd 1 1 1\n").0, 1);
+        assert_eq!(error("newmtl a\n\nKd 1 x 1\n"), (3, String::from("Invalid number 'x'")));
+        assert_eq!(error("newmtl a\nNs\n"), (2, String::from("Missing value")));
+        assert_eq!(error("newmtl a\nnewmtl\n"), (2, String::from("newmtl without material name")));
+        assert_eq!(error("newmtl a\nmap_Kd -bm 1.0\n"), (2, String::from("Missing texture file name")));
+        assert_eq!(error("newmtl a\nmap_Kd -mm 1\n"), (2, String::from("Missing value for texture option '-mm'")));
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::path::Path;
+use glm::{Vec2, Vec3};
+use num_traits::Zero;
+use crate::renderer::error::RendererError;
+use crate::renderer::mesh::Topology;
+use crate::renderer::vertex::Vertex;
+
+/// Geometry parsed from a Wavefront OBJ file, split into one submesh per
+/// `usemtl` section and element type.
+pub struct ObjData {
+    /// `mtllib` references, relative to the OBJ file.
+    pub material_libs: Vec<String>,
+    pub submeshes: Vec<ObjSubmesh>,
+}
+
+/// Faces, lines or points sharing a material, deduplicated into the
+/// `Vertex` + `u32` index layout used by `Mesh`.
+pub struct ObjSubmesh {
+    pub material: Option<String>,
+    /// `Triangles` for faces, `Lines` for `l` and `Points` for `p` elements.
+    pub topology: Topology,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    
+    lookup: HashMap<FaceCorner, u32>,
+}
+
+impl ObjData {
+    /// Collapses all face submeshes into a single vertex and index list.
+    /// Lines and points are left out.
+    pub fn merged(self) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for submesh in self.submeshes.into_iter().filter(|s| s.topology == Topology::Triangles) {
+            let offset = vertices.len() as u32;
+            vertices.extend(submesh.vertices);
+            indices.extend(submesh.indices.iter().map(|i| i + offset));
+        }
+        (vertices, indices)
+    }
+}
+
+impl ObjSubmesh {
+    fn new(material: Option<String>, topology: Topology) -> Self {
+        Self { material, topology, vertices: Vec::new(), indices: Vec::new(), lookup: HashMap::new() }
+    }
+
+    /// Index of the vertex for `corner`, adding it if it's new.
+    fn add_corner(&mut self, corner: FaceCorner, positions: &[Vec3], uvs: &[Vec2], normals: &[Vec3]) -> u32 {
+        let vertices = &mut self.vertices;
+        *self.lookup.entry(corner).or_insert_with(|| {
+            vertices.push(Vertex::new(
+                positions[corner.v],
+                corner.vn.map_or(Vec3::zero(), |i| normals[i]),
+                corner.vt.map_or(Vec2::zero(), |i| uvs[i]),
+            ));
+            (vertices.len() - 1) as u32
+        })
+    }
+}
+
+/// The submesh collecting elements of `topology` for `material`. Elements for
+/// a material can be spread over the file, this keeps them together.
+fn submesh_for<'a>(submeshes: &'a mut Vec<ObjSubmesh>, material: &Option<String>, topology: Topology) -> &'a mut ObjSubmesh {
+    let index = match submeshes.iter().position(|s| &s.material == material && s.topology == topology) {
+        Some(index) => index,
+        None => {
+            submeshes.push(ObjSubmesh::new(material.clone(), topology));
+            submeshes.len() - 1
+        }
+    };
+    &mut submeshes[index]
+}
+
+/// A single corner of a face: indices into the position, uv and normal lists.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceCorner {
+    v: usize,
+    vt: Option<usize>,
+    vn: Option<usize>,
+}
+
+pub fn load(path: &Path) -> Result<ObjData, RendererError> {
+    let src = std::fs::read_to_string(path).map_err(|e| RendererError::io(path, e))?;
+    parse(&src).map_err(|(line, msg)| RendererError::asset(path, Some(line), msg))
+}
+
+/// Parses OBJ source. Errors carry the 1-based line number they occurred on.
+pub fn parse(src: &str) -> Result<ObjData, (usize, String)> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut material_libs: Vec<String> = Vec::new();
+    let mut submeshes: Vec<ObjSubmesh> = Vec::new();
+    let mut material: Option<String> = None;
+
+    for (line_idx, line) in src.lines().enumerate() {
+        let line_nr = line_idx + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&mut tokens, 3).map_err(|e| (line_nr, e))?;
+                positions.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                // the optional w component is ignored
+                let [u, v] = parse_floats::<2>(&mut tokens, 1).map_err(|e| (line_nr, e))?;
+                uvs.push(Vec2::new(u, v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&mut tokens, 3).map_err(|e| (line_nr, e))?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for token in tokens {
+                    let corner = parse_corner(token, positions.len(), uvs.len(), normals.len())
+                        .map_err(|e| (line_nr, e))?;
+                    corners.push(corner);
+                }
+                if corners.len() < 3 {
+                    return Err((line_nr, format!("Face needs at least 3 vertices, got {}", corners.len())));
+                }
+
+                let submesh = submesh_for(&mut submeshes, &material, Topology::Triangles);
+                let face_indices: Vec<u32> = corners.into_iter()
+                    .map(|corner| submesh.add_corner(corner, &positions, &uvs, &normals))
+                    .collect();
+
+                // fan triangulation, assumes convex polygons
+                for i in 1..face_indices.len() - 1 {
+                    submesh.indices.push(face_indices[0]);
+                    submesh.indices.push(face_indices[i]);
+                    submesh.indices.push(face_indices[i + 1]);
+                }
+            }
+            "l" | "p" => {
+                let mut corners = Vec::new();
+                for token in tokens {
+                    let corner = parse_corner(token, positions.len(), uvs.len(), normals.len())
+                        .map_err(|e| (line_nr, e))?;
+                    if corner.vn.is_some() {
+                        return Err((line_nr, format!("Normals are not allowed in '{keyword}' elements")));
+                    }
+                    corners.push(corner);
+                }
+
+                let topology = if keyword == "l" { Topology::Lines } else { Topology::Points };
+                if topology == Topology::Lines && corners.len() < 2 {
+                    return Err((line_nr, format!("Line needs at least 2 vertices, got {}", corners.len())));
+                }
+                let submesh = submesh_for(&mut submeshes, &material, topology);
+                let element_indices: Vec<u32> = corners.into_iter()
+                    .map(|corner| submesh.add_corner(corner, &positions, &uvs, &normals))
+                    .collect();
+
+                match topology {
+                    // polylines are split into segments so they can share one submesh
+                    Topology::Lines => submesh.indices.extend(element_indices.windows(2).flatten()),
+                    _ => submesh.indices.extend(element_indices),
+                }
+            }
+            "mtllib" => {
+                // file names may contain spaces
+                let libs = line.trim_start()[keyword.len()..].trim();
+                if libs.is_empty() {
+                    return Err((line_nr, String::from("mtllib without file name")));
+                }
+                material_libs.push(libs.to_string());
+            }
+            "usemtl" => {
+                let name = line.trim_start()[keyword.len()..].trim();
+                if name.is_empty() {
+                    return Err((line_nr, String::from("usemtl without material name")));
+                }
+                material = Some(name.to_string());
+            }
+            // grouping and smoothing statements don't affect the geometry
+            _ => {}
+        }
+    }
+
+    submeshes.retain(|s| !s.indices.is_empty());
+    Ok(ObjData { material_libs, submeshes })
+}
+
+/// Reads up to `N` floats, requiring at least `required` of them. Missing
+/// optional components are zero, extra components are ignored.
+fn parse_floats<'a, const N: usize>(tokens: &mut impl Iterator<Item = &'a str>, required: usize) -> Result<[f32; N], String> {
+    let mut result = [0.0; N];
+    for (i, value) in result.iter_mut().enumerate() {
+        match tokens.next() {
+            Some(token) => {
+                *value = token.parse::<f32>()
+                    .map_err(|_| format!("Invalid number '{token}'"))?;
+            }
+            None if i < required => {
+                return Err(format!("Expected {required} components, got {i}"));
+            }
+            None => break,
+        }
+    }
+    Ok(result)
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based indices.
+fn parse_corner(token: &str, positions: usize, uvs: usize, normals: usize) -> Result<FaceCorner, String> {
+    let mut parts = token.split('/');
+    let v = match parts.next() {
+        Some(part) if !part.is_empty() => resolve_index(part, positions, "position")?,
+        _ => return Err(format!("Missing position index in '{token}'")),
+    };
+    let vt = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, uvs, "uv")?),
+        _ => None,
+    };
+    let vn = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, normals, "normal")?),
+        _ => None,
+    };
+    if parts.next().is_some() {
+        return Err(format!("Too many components in face vertex '{token}'"));
+    }
+    Ok(FaceCorner { v, vt, vn })
+}
+
+/// Converts a 1-based (or negative, relative to the end) OBJ index to a zero-based one.
+fn resolve_index(part: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let index: i64 = part.parse()
+        .map_err(|_| format!("Invalid {kind} index '{part}'"))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        count as i64 + index
+    } else {
+        return Err(format!("Invalid {kind} index 0, indices start at 1"));
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{kind} index {index} out of range, {count} defined so far"));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+";
+
+    fn single(src: &str) -> ObjSubmesh {
+        let mut obj = parse(src).unwrap();
+        assert_eq!(obj.submeshes.len(), 1);
+        obj.submeshes.remove(0)
+    }
+
+    #[test]
+    fn mixed_corner_forms() {
+        let submesh = single(&format!("{SQUARE}f 1 2/2 3//1\nf 1/1/1 3/3 4\n"));
+        assert_eq!(submesh.topology, Topology::Triangles);
+        assert_eq!(submesh.indices.len(), 6);
+
+        let first = &submesh.vertices[submesh.indices[0] as usize];
+        assert_eq!(first.v, Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(first.vt, Vec2::zero());
+        assert_eq!(first.vn, Vec3::zero());
+
+        let second = &submesh.vertices[submesh.indices[1] as usize];
+        assert_eq!(second.vt, Vec2::new(1.0, 0.0));
+        assert_eq!(second.vn, Vec3::zero());
+
+        let third = &submesh.vertices[submesh.indices[2] as usize];
+        assert_eq!(third.vt, Vec2::zero());
+        assert_eq!(third.vn, Vec3::new(0.0, 0.0, 1.0));
+
+        let fourth = &submesh.vertices[submesh.indices[3] as usize];
+        assert_eq!(fourth.vt, Vec2::new(0.0, 0.0));
+        assert_eq!(fourth.vn, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn negative_indices_are_relative() {
+        let submesh = single(&format!("{SQUARE}f -4/-4/-1 -3/-3/-1 -2/-2/-1\n"));
+        let positions: Vec<Vec3> = submesh.indices.iter().map(|&i| submesh.vertices[i as usize].v).collect();
+        assert_eq!(positions, [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)]);
+        assert_eq!(submesh.vertices[submesh.indices[2] as usize].vt, Vec2::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let submesh = single("v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n");
+        assert_eq!(submesh.vertices.len(), 5);
+        assert_eq!(submesh.indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn shared_corners_are_deduplicated() {
+        let submesh = single(&format!("{SQUARE}f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\nf 1/2/1 2/2/1 3/3/1\n"));
+        // 1/2/1 differs from 1/1/1 by its uv and gets its own vertex
+        assert_eq!(submesh.vertices.len(), 5);
+        assert_eq!(submesh.indices, [0, 1, 2, 0, 2, 3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn submeshes_are_split_by_material_and_topology() {
+        let obj = parse(&format!("{SQUARE}usemtl a\nf 1 2 3\nl 1 2 3\nusemtl b\nf 1 3 4\nusemtl a\nf 1 3 4\n")).unwrap();
+        let summary: Vec<(Option<&str>, Topology, usize)> = obj.submeshes.iter()
+            .map(|s| (s.material.as_deref(), s.topology, s.indices.len()))
+            .collect();
+        assert_eq!(summary, [
+            (Some("a"), Topology::Triangles, 6),
+            (Some("a"), Topology::Lines, 4),
+            (Some("b"), Topology::Triangles, 3),
+        ]);
+    }
+
+    #[test]
+    fn merged_keeps_only_faces() {
+        let (vertices, indices) = parse(&format!("{SQUARE}usemtl a\nf 1 2 3\np 4\nusemtl b\nf 1 3 4\n")).unwrap().merged();
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn errors_carry_line_numbers() {
+        let cases = [
+            ("v 0 0 0\nv 1 0\n", 2, "Expected 3 components"),
+            ("v 0 0 0\n\n# comment\nv x 0 0\n", 4, "Invalid number 'x'"),
+            (&format!("{SQUARE}f 1 2 5\n") as &str, 10, "position index 5 out of range"),
+            (&format!("{SQUARE}f 1 2 -5\n") as &str, 10, "position index -5 out of range"),
+            (&format!("{SQUARE}f 0 1 2\n") as &str, 10, "indices start at 1"),
+            (&format!("{SQUARE}f 1/1/2 2 3\n") as &str, 10, "normal index 2 out of range"),
+            (&format!("{SQUARE}f 1 2\n") as &str, 10, "at least 3 vertices"),
+            (&format!("{SQUARE}f 1/1/1/1 2 3\n") as &str, 10, "Too many components"),
+            (&format!("{SQUARE}l 1//1 2\n") as &str, 10, "Normals are not allowed"),
+            ("usemtl\n", 1, "usemtl without material name"),
+        ];
+        for (src, line, message) in cases {
+            match parse(src) {
+                Ok(_) => panic!("parsing {src:?} should fail"),
+                Err((error_line, error)) => {
+                    assert_eq!(error_line, line, "{error}");
+                    assert!(error.contains(message), "'{error}' should contain '{message}'");
+                }
+            }
+        }
+    }
+}
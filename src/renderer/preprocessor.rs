@@ -0,0 +1,313 @@
+use std::path::{Path, PathBuf};
+
+/// Resolves `#include "file"` (or `<file>`) directives in GLSL sources.
+/// Includes are looked up relative to the including file first, then in the
+/// search paths in order. Files containing `#pragma once` are included only once.
+#[derive(Clone, Debug)]
+pub struct Preprocessor {
+    search_paths: Vec<PathBuf>,
+}
+
+/// A shader source with all includes expanded.
+#[derive(Clone, Debug)]
+pub struct ExpandedSource {
+    pub source: String,
+    /// Every file that went into the source, the root file first.
+    pub files: Vec<PathBuf>,
+    /// (index into `files`, 1-based line) for every line of `source`.
+    line_map: Vec<(usize, usize)>,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new().with_search_path("res/shaders")
+    }
+}
+
+impl Preprocessor {
+    /// Preprocessor without search paths, includes are only found next to the including file.
+    pub fn new() -> Self {
+        Self { search_paths: Vec::new() }
+    }
+
+    pub fn with_search_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    pub fn add_search_path<P: Into<PathBuf>>(&mut self, path: P) {
+        self.search_paths.push(path.into());
+    }
+
+    pub fn process(&self, path: &Path) -> Result<ExpandedSource, String> {
+        let mut expanded = ExpandedSource { source: String::new(), files: Vec::new(), line_map: Vec::new() };
+        let mut stack = Vec::new();
+        let mut once = Vec::new();
+        self.expand(path, &mut expanded, &mut stack, &mut once)?;
+        Ok(expanded)
+    }
+
+    fn expand(&self, path: &Path, out: &mut ExpandedSource, stack: &mut Vec<PathBuf>, once: &mut Vec<PathBuf>) -> Result<(), String> {
+        let canonical = path.canonicalize()
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        if once.contains(&canonical) {
+            return Ok(());
+        }
+        if let Some(start) = stack.iter().position(|p| *p == canonical) {
+            let cycle: Vec<String> = stack[start..].iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+            return Err(format!("Include cycle: {}", cycle.join(" -> ")));
+        }
+
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let file = match out.files.iter().position(|f| f == path) {
+            Some(file) => file,
+            None => {
+                out.files.push(path.to_path_buf());
+                out.files.len() - 1
+            }
+        };
+
+        stack.push(canonical.clone());
+        for (line_idx, line) in src.lines().enumerate() {
+            let line_nr = line_idx + 1;
+            let trimmed = line.trim();
+            if let Some(rest) = directive(trimmed, "include") {
+                let name = parse_include_name(rest)
+                    .ok_or(format!("{}:{}: Malformed include '{}'", path.display(), line_nr, trimmed))?;
+                let included = self.resolve(path, name)
+                    .ok_or(format!("{}:{}: Could not find include '{}'", path.display(), line_nr, name))?;
+                self.expand(&included, out, stack, once)
+                    .map_err(|e| format!("{}\n  included from {}:{}", e, path.display(), line_nr))?;
+                continue;
+            }
+            if directive(trimmed, "pragma").is_some_and(|rest| rest.trim() == "once") {
+                once.push(canonical.clone());
+                // keep the line so line numbers stay the same
+                out.push_line("", file, line_nr);
+                continue;
+            }
+            out.push_line(line, file, line_nr);
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    fn resolve(&self, including: &Path, name: &str) -> Option<PathBuf> {
+        let relative = including.parent().map(|dir| dir.join(name));
+        relative.into_iter()
+            .chain(self.search_paths.iter().map(|dir| dir.join(name)))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+impl ExpandedSource {
+    fn push_line(&mut self, line: &str, file: usize, line_nr: usize) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.line_map.push((file, line_nr));
+    }
+
+    /// Inserts `lines` right after the `#version` directive, or at the top if
+    /// there is none. They are reported as coming from the `#version` line.
+    pub fn insert_after_version(&mut self, lines: &str) {
+        let version = self.source.lines()
+            .position(|line| directive(line.trim(), "version").is_some());
+        let (index, origin) = match version {
+            Some(index) => (index + 1, self.line_map[index]),
+            None => (0, (0, 1)),
+        };
+
+        let offset: usize = self.source.lines().take(index).map(|line| line.len() + 1).sum();
+        let mut text = String::new();
+        for line in lines.lines() {
+            text.push_str(line);
+            text.push('\n');
+        }
+        self.source.insert_str(offset, &text);
+        self.line_map.splice(index..index, std::iter::repeat_n(origin, lines.lines().count()));
+    }
+
+    /// File and line a 1-based line of the expanded source came from.
+    pub fn origin(&self, line: usize) -> Option<(&Path, usize)> {
+        let &(file, line) = self.line_map.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    /// Replaces the `0:LINE` / `0(LINE)` locations drivers put into compile
+    /// logs with the original `file:line`.
+    pub fn rewrite_log(&self, log: &str) -> String {
+        log.lines().map(|line| {
+            match find_location(line) {
+                Some((start, end, expanded_line)) => match self.origin(expanded_line) {
+                    Some((file, original)) => format!("{}{}:{}{}", &line[..start], file.display(), original, &line[end..]),
+                    None => line.to_string(),
+                },
+                None => line.to_string(),
+            }
+        }).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// The rest of the line if it is `#name ...`, whitespace after `#` is allowed.
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix('#')?.trim_start().strip_prefix(name)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+}
+
+fn parse_include_name(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let (open, close) = match rest.chars().next()? {
+        '"' => ('"', '"'),
+        '<' => ('<', '>'),
+        _ => return None,
+    };
+    let inner = rest.strip_prefix(open)?;
+    let end = inner.find(close)?;
+    let after = inner[end + 1..].trim();
+    (end > 0 && (after.is_empty() || after.starts_with("//"))).then_some(&inner[..end])
+}
+
+/// Finds the first `0:LINE` (Mesa, AMD, Intel) or `0(LINE)` (NVIDIA) in a log
+/// line. Returns the byte range to replace and the line number.
+fn find_location(line: &str) -> Option<(usize, usize, usize)> {
+    let bytes = line.as_bytes();
+    for start in 0..bytes.len() {
+        if bytes[start] != b'0' || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+            continue;
+        }
+        let open = *bytes.get(start + 1)?;
+        if open != b':' && open != b'(' {
+            continue;
+        }
+        let digits_start = start + 2;
+        let digits_end = digits_start + bytes[digits_start..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits_end == digits_start {
+            continue;
+        }
+        let end = if open == b'(' {
+            if bytes.get(digits_end) != Some(&b')') {
+                continue;
+            }
+            digits_end + 1
+        } else {
+            digits_end
+        };
+        let number = line[digits_start..digits_end].parse().ok()?;
+        return Some((start, end, number));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory under the system temp dir, removed again on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("gl_rust_preprocessor_{}_{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn includes_are_expanded_in_place() {
+        let dir = TempDir::new("expand");
+        dir.write("lib/math.glsl", "float twice(float x) { return 2.0 * x; }\n");
+        dir.write("common/light.glsl", "#include \"../lib/math.glsl\"\nvec3 light;\n");
+        let root = dir.write("main.frag", "#version 460\n#include \"common/light.glsl\"\nvoid main() {}\n");
+
+        let expanded = Preprocessor::new().process(&root).unwrap();
+        assert_eq!(expanded.source, "#version 460\nfloat twice(float x) { return 2.0 * x; }\nvec3 light;\nvoid main() {}\n");
+        assert_eq!(expanded.files, [root.clone(), dir.0.join("common/light.glsl"), dir.0.join("common/../lib/math.glsl")]);
+    }
+
+    #[test]
+    fn search_paths_are_used_after_the_including_directory() {
+        let dir = TempDir::new("search");
+        dir.write("shared/camera.glsl", "// shared\n");
+        dir.write("shaders/camera.glsl", "// local\n");
+        let root = dir.write("shaders/main.vert", "#include <camera.glsl>\n");
+        let other = dir.write("other/main.vert", "#include <camera.glsl>\n");
+
+        let preprocessor = Preprocessor::new().with_search_path(dir.0.join("shared"));
+        assert_eq!(preprocessor.process(&root).unwrap().source, "// local\n");
+        assert_eq!(preprocessor.process(&other).unwrap().source, "// shared\n");
+        assert!(Preprocessor::new().process(&other).unwrap_err().contains("Could not find include 'camera.glsl'"));
+    }
+
+    #[test]
+    fn pragma_once_includes_a_file_once() {
+        let dir = TempDir::new("once");
+        dir.write("once.glsl", "#pragma once\nuniform float a;\n");
+        dir.write("twice.glsl", "uniform float b;\n");
+        let root = dir.write("main.frag", "#include \"once.glsl\"\n#include \"once.glsl\"\n#include \"twice.glsl\"\n#include \"twice.glsl\"\n");
+
+        let expanded = Preprocessor::new().process(&root).unwrap();
+        // the pragma line is kept empty so line numbers don't shift
+        assert_eq!(expanded.source, "\nuniform float a;\nuniform float b;\nuniform float b;\n");
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let dir = TempDir::new("cycle");
+        dir.write("a.glsl", "#include \"b.glsl\"\n");
+        dir.write("b.glsl", "// b\n#include \"a.glsl\"\n");
+        let root = dir.write("main.frag", "#include \"a.glsl\"\n");
+
+        let error = Preprocessor::new().process(&root).unwrap_err();
+        assert!(error.contains("Include cycle"), "{}", error);
+        assert!(error.contains("a.glsl -> ") && error.contains("b.glsl -> "), "{}", error);
+        assert!(error.contains("included from"), "{}", error);
+    }
+
+    #[test]
+    fn malformed_includes_are_reported_with_their_line() {
+        let dir = TempDir::new("malformed");
+        let root = dir.write("main.frag", "#version 460\n#include camera.glsl\n");
+
+        let error = Preprocessor::new().process(&root).unwrap_err();
+        assert!(error.contains(&format!("{}:2: Malformed include", root.display())), "{}", error);
+    }
+
+    #[test]
+    fn log_lines_are_mapped_back_to_their_files() {
+        let dir = TempDir::new("log");
+        let include = dir.write("inc.glsl", "// first\nfloat broken = ;\n");
+        let root = dir.write("main.frag", "#version 460\n#include \"inc.glsl\"\nvoid main() { oops; }\n");
+
+        let mut expanded = Preprocessor::new().process(&root).unwrap();
+        expanded.insert_after_version("#define A\n#define B");
+        // expanded: version, A, B, first, broken, main
+        assert_eq!(expanded.origin(2), Some((root.as_path(), 1)));
+        assert_eq!(expanded.origin(5), Some((include.as_path(), 2)));
+        assert_eq!(expanded.origin(6), Some((root.as_path(), 3)));
+        assert_eq!(expanded.origin(7), None);
+
+        let log = "0:5(16): error: syntax error\nERROR: 0:6: 'oops' : undeclared identifier\nno location here";
+        let expected = format!(
+            "{}:2(16): error: syntax error\nERROR: {}:3: 'oops' : undeclared identifier\nno location here",
+            include.display(), root.display(),
+        );
+        assert_eq!(expanded.rewrite_log(log), expected);
+        assert_eq!(expanded.rewrite_log("0(6) : error C1008: undefined variable"), format!("{}:3 : error C1008: undefined variable", root.display()));
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+use glm::{Vec2, Vec3};
+use num_traits::Zero;
+use crate::renderer::mesh::Topology;
+use crate::renderer::mesh_data::MeshData;
+use crate::renderer::vertex::Vertex;
+
+// All shapes are centered on the origin, use counter clockwise winding seen
+// from the outside and come with normals and uvs.
+impl MeshData {
+    pub fn quad() -> Self {
+        let vertices: Vec<Vertex> = vec![
+            Vertex::new(Vec3::new(-0.5, -0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5, -0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new(-0.5,  0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 1.0)),
+        ];
+
+        let indices: Vec<u32> = vec![
+            0, 1, 2,
+            2, 3, 0,
+        ];
+
+        MeshData::new(vertices, indices)
+    }
+
+    pub fn cube() -> Self {
+        let vertices: Vec<Vertex> = vec![
+            // Front face (+Z) - Red
+            Vertex::new(Vec3::new(-0.5, -0.5,  0.5), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5, -0.5,  0.5), Vec3::new(0.0, 0.0, 1.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5,  0.5), Vec3::new(0.0, 0.0, 1.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new(-0.5,  0.5,  0.5), Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 1.0)),
+            
+            // Back face (-Z) - Green
+            Vertex::new(Vec3::new( 0.5, -0.5, -0.5), Vec3::new(0.0, 0.0, -1.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.0, 0.0, -1.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new(-0.5,  0.5, -0.5), Vec3::new(0.0, 0.0, -1.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5, -0.5), Vec3::new(0.0, 0.0, -1.0), Vec2::new(0.0, 1.0)),
+            
+            // Top face (+Y) - Blue
+            Vertex::new(Vec3::new(-0.5,  0.5,  0.5), Vec3::new(0.0, 1.0, 0.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5,  0.5), Vec3::new(0.0, 1.0, 0.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5, -0.5), Vec3::new(0.0, 1.0, 0.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new(-0.5,  0.5, -0.5), Vec3::new(0.0, 1.0, 0.0), Vec2::new(0.0, 1.0)),
+            
+            // Bottom face (-Y) - Yellow
+            Vertex::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(0.0, -1.0, 0.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5, -0.5, -0.5), Vec3::new(0.0, -1.0, 0.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5, -0.5,  0.5), Vec3::new(0.0, -1.0, 0.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new(-0.5, -0.5,  0.5), Vec3::new(0.0, -1.0, 0.0), Vec2::new(0.0, 1.0)),
+            
+            // Right face (+X) - Magenta
+            Vertex::new(Vec3::new( 0.5, -0.5,  0.5), Vec3::new(1.0, 0.0, 0.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5, -0.5, -0.5), Vec3::new(1.0, 0.0, 0.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5, -0.5), Vec3::new(1.0, 0.0, 0.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new( 0.5,  0.5,  0.5), Vec3::new(1.0, 0.0, 0.0), Vec2::new(0.0, 1.0)),
+            
+            // Left face (-X) - Cyan
+            Vertex::new(Vec3::new(-0.5, -0.5, -0.5), Vec3::new(-1.0, 0.0, 0.0), Vec2::new(0.0, 0.0)),
+            Vertex::new(Vec3::new(-0.5, -0.5,  0.5), Vec3::new(-1.0, 0.0, 0.0), Vec2::new(1.0, 0.0)),
+            Vertex::new(Vec3::new(-0.5,  0.5,  0.5), Vec3::new(-1.0, 0.0, 0.0), Vec2::new(1.0, 1.0)),
+            Vertex::new(Vec3::new(-0.5,  0.5, -0.5), Vec3::new(-1.0, 0.0, 0.0), Vec2::new(0.0, 1.0)),
+        ];
+        
+        let indices: Vec<u32> = vec![
+            // Front
+            0, 1, 2,  2, 3, 0,
+            // Back
+            4, 5, 6,  6, 7, 4,
+            // Top
+            8, 9, 10,  10, 11, 8,
+            // Bottom
+            12, 13, 14,  14, 15, 12,
+            // Right
+            16, 17, 18,  18, 19, 16,
+            // Left
+            20, 21, 22,  22, 23, 20,
+        ];
+        
+        MeshData::new(vertices, indices)
+    }
+    
+    /// Flat grid in the xz plane facing +y, subdivided into `x_segments` by `z_segments` cells.
+    pub fn grid(width: f32, depth: f32, x_segments: u32, z_segments: u32) -> Self {
+        let x_segments = x_segments.max(1);
+        let z_segments = z_segments.max(1);
+        
+        let mut vertices = Vec::new();
+        for j in 0..=z_segments {
+            for i in 0..=x_segments {
+                let u = i as f32 / x_segments as f32;
+                let v = j as f32 / z_segments as f32;
+                vertices.push(Vertex::new(
+                    Vec3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    Vec2::new(u, v),
+                ));
+            }
+        }
+        
+        let row = x_segments + 1;
+        let mut indices = Vec::new();
+        for j in 0..z_segments {
+            for i in 0..x_segments {
+                let a = j * row + i;
+                let b = a + 1;
+                let c = b + row;
+                let d = a + row;
+                indices.extend_from_slice(&[a, b, c, c, d, a]);
+            }
+        }
+        
+        MeshData::new(vertices, indices)
+    }
+    
+    pub fn plane(width: f32, depth: f32) -> Self {
+        Self::grid(width, depth, 1, 1)
+    }
+    
+    /// Sphere made of `rings` latitude bands of `segments` quads each.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let rings = rings.max(2);
+        let profile: Vec<ProfilePoint> = (0..=rings).map(|i| {
+            let phi = PI * i as f32 / rings as f32;
+            ProfilePoint {
+                radius: radius * phi.sin(),
+                y: radius * phi.cos(),
+                normal: Vec2::new(phi.sin(), phi.cos()),
+                v: 1.0 - i as f32 / rings as f32,
+            }
+        }).collect();
+        
+        revolve(&profile, segments)
+    }
+    
+    /// Sphere made by splitting the faces of an icosahedron `subdivisions` times,
+    /// giving evenly sized triangles.
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Self {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut points: Vec<Vec3> = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+        ].iter().map(|&(x, y, z)| glm::normalize(Vec3::new(x, y, z))).collect();
+        
+        let mut faces: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32, points: &mut Vec<Vec3>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    points.push(glm::normalize((points[a as usize] + points[b as usize]) * 0.5));
+                    (points.len() - 1) as u32
+                })
+            };
+            
+            let mut subdivided = Vec::with_capacity(faces.len() * 4);
+            for [a, b, c] in faces {
+                let ab = midpoint(a, b, &mut points);
+                let bc = midpoint(b, c, &mut points);
+                let ca = midpoint(c, a, &mut points);
+                subdivided.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+            }
+            faces = subdivided;
+        }
+        
+        // spherical uvs, triangles crossing the seam get their own vertices
+        let mut vertices = Vec::with_capacity(faces.len() * 3);
+        for face in faces {
+            let normals = face.map(|i| points[i as usize]);
+            let mut us = normals.map(|n| 0.5 + n.x.atan2(n.z) / TAU);
+            
+            let max = us.iter().cloned().fold(f32::MIN, f32::max);
+            if max - us.iter().cloned().fold(f32::MAX, f32::min) > 0.5 {
+                for u in us.iter_mut() {
+                    if *u < 0.5 {
+                        *u += 1.0;
+                    }
+                }
+            }
+            // the poles have no longitude, use the one of the opposite edge
+            for corner in 0..3 {
+                if normals[corner].x.abs() < 1e-6 && normals[corner].z.abs() < 1e-6 {
+                    us[corner] = (us[(corner + 1) % 3] + us[(corner + 2) % 3]) * 0.5;
+                }
+            }
+            
+            for corner in 0..3 {
+                let n = normals[corner];
+                let v = 0.5 + n.y.clamp(-1.0, 1.0).asin() / PI;
+                vertices.push(Vertex::new(n * radius, n, Vec2::new(us[corner], v)));
+            }
+        }
+        
+        let indices = (0..vertices.len() as u32).collect();
+        let mut data = MeshData::new(vertices, indices);
+        data.weld();
+        data
+    }
+    
+    /// Cylinder along the y axis with capped ends.
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let h = height * 0.5;
+        let profile = [
+            ProfilePoint { radius, y: h, normal: Vec2::new(1.0, 0.0), v: 1.0 },
+            ProfilePoint { radius, y: -h, normal: Vec2::new(1.0, 0.0), v: 0.0 },
+        ];
+        
+        let mut data = revolve(&profile, segments);
+        data.merge(&disc(radius, h, segments, true)).expect("caps and sides are triangle lists");
+        data.merge(&disc(radius, -h, segments, false)).expect("caps and sides are triangle lists");
+        data
+    }
+    
+    /// Cone along the y axis with the tip at the top and a capped base.
+    pub fn cone(radius: f32, height: f32, segments: u32) -> Self {
+        let h = height * 0.5;
+        let slope = glm::normalize(Vec2::new(height, radius));
+        let profile = [
+            ProfilePoint { radius: 0.0, y: h, normal: slope, v: 1.0 },
+            ProfilePoint { radius, y: -h, normal: slope, v: 0.0 },
+        ];
+        
+        let mut data = revolve(&profile, segments);
+        data.merge(&disc(radius, -h, segments, false)).expect("caps and sides are triangle lists");
+        data
+    }
+    
+    /// Cylinder of `height` along the y axis with hemispheres on both ends.
+    /// `rings` is the number of bands per hemisphere.
+    pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Self {
+        let rings = rings.max(1);
+        let h = height * 0.5;
+        let arc = PI * 0.5 * radius;
+        let length = 2.0 * arc + height;
+        
+        let mut profile = Vec::new();
+        for (offset, start, travelled) in [(h, 0.0, 0.0), (-h, PI * 0.5, arc + height)] {
+            for i in 0..=rings {
+                let t = i as f32 / rings as f32;
+                let phi = start + PI * 0.5 * t;
+                profile.push(ProfilePoint {
+                    radius: radius * phi.sin(),
+                    y: radius * phi.cos() + offset,
+                    normal: Vec2::new(phi.sin(), phi.cos()),
+                    v: 1.0 - (travelled + arc * t) / length,
+                });
+            }
+        }
+        
+        revolve(&profile, segments)
+    }
+    
+    /// Torus around the y axis. `major_radius` is the distance from the center
+    /// to the middle of the tube, `minor_radius` the radius of the tube.
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, rings: u32) -> Self {
+        let rings = rings.max(3);
+        // walk around the tube starting at the top, moving outwards first
+        let profile: Vec<ProfilePoint> = (0..=rings).map(|i| {
+            let a = PI * 0.5 - TAU * i as f32 / rings as f32;
+            ProfilePoint {
+                radius: major_radius + minor_radius * a.cos(),
+                y: minor_radius * a.sin(),
+                normal: Vec2::new(a.cos(), a.sin()),
+                v: 1.0 - i as f32 / rings as f32,
+            }
+        }).collect();
+        
+        revolve(&profile, segments)
+    }
+}
+
+// Debug and point cloud geometry. These have no normals or uvs and are not indexed.
+impl MeshData {
+    pub fn points(positions: &[Vec3]) -> Self {
+        MeshData::non_indexed(positions.iter().map(|&p| bare_vertex(p)).collect(), Topology::Points)
+    }
+
+    /// Line through `positions` in order, back to the first one if `closed`.
+    pub fn polyline(positions: &[Vec3], closed: bool) -> Self {
+        let topology = if closed { Topology::LineLoop } else { Topology::LineStrip };
+        MeshData::non_indexed(positions.iter().map(|&p| bare_vertex(p)).collect(), topology)
+    }
+
+    /// Square grid of lines on the xz plane with `divisions` cells per side.
+    pub fn line_grid(size: f32, divisions: u32) -> Self {
+        let divisions = divisions.max(1);
+        let half = size * 0.5;
+        let mut vertices = Vec::with_capacity(4 * (divisions as usize + 1));
+        for i in 0..=divisions {
+            let t = -half + size * i as f32 / divisions as f32;
+            vertices.push(bare_vertex(Vec3::new(t, 0.0, -half)));
+            vertices.push(bare_vertex(Vec3::new(t, 0.0, half)));
+            vertices.push(bare_vertex(Vec3::new(-half, 0.0, t)));
+            vertices.push(bare_vertex(Vec3::new(half, 0.0, t)));
+        }
+        MeshData::non_indexed(vertices, Topology::Lines)
+    }
+
+    /// The x, y and z axes as three lines of `length` starting at the origin.
+    pub fn axes(length: f32) -> Self {
+        let vertices = [Vec3::new(length, 0.0, 0.0), Vec3::new(0.0, length, 0.0), Vec3::new(0.0, 0.0, length)]
+            .iter()
+            .flat_map(|&axis| [bare_vertex(Vec3::zero()), bare_vertex(axis)])
+            .collect();
+        MeshData::non_indexed(vertices, Topology::Lines)
+    }
+}
+
+/// A point of a shape's outline in the (distance from y axis, y) plane.
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    /// (radial, y) components of the normal
+    normal: Vec2,
+    v: f32,
+}
+
+/// Sweeps `profile` around the y axis. The profile has to run downwards along
+/// the outside of the shape for the faces to point outwards. Points on the
+/// axis (radius 0) don't produce triangles.
+fn revolve(profile: &[ProfilePoint], segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let row = segments + 1;
+    
+    let mut vertices = Vec::new();
+    for point in profile {
+        // the first column is repeated at the end so the uvs can wrap
+        for s in 0..=segments {
+            let u = s as f32 / segments as f32;
+            let theta = TAU * u;
+            let dir = Vec3::new(theta.sin(), 0.0, theta.cos());
+            vertices.push(Vertex::new(
+                dir * point.radius + Vec3::new(0.0, point.y, 0.0),
+                glm::normalize(dir * point.normal.x + Vec3::new(0.0, point.normal.y, 0.0)),
+                Vec2::new(u, point.v),
+            ));
+        }
+    }
+    
+    let mut indices = Vec::new();
+    for (r, pair) in profile.windows(2).enumerate() {
+        let r = r as u32;
+        for s in 0..segments {
+            let a = r * row + s;
+            let b = a + row;
+            let c = b + 1;
+            let d = a + 1;
+            if pair[1].radius > 0.0 {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+            if pair[0].radius > 0.0 {
+                indices.extend_from_slice(&[a, c, d]);
+            }
+        }
+    }
+    
+    // drops the unused vertices on the axis
+    let mut data = MeshData::new(vertices, indices);
+    data.weld();
+    data
+}
+
+/// Flat disc at height `y` facing up or down.
+fn disc(radius: f32, y: f32, segments: u32, up: bool) -> MeshData {
+    let segments = segments.max(3);
+    let normal = Vec3::new(0.0, if up { 1.0 } else { -1.0 }, 0.0);
+    
+    let mut vertices = vec![Vertex::new(Vec3::new(0.0, y, 0.0), normal, Vec2::new(0.5, 0.5))];
+    for s in 0..=segments {
+        let theta = TAU * s as f32 / segments as f32;
+        let (sin, cos) = theta.sin_cos();
+        vertices.push(Vertex::new(
+            Vec3::new(sin * radius, y, cos * radius),
+            normal,
+            Vec2::new(0.5 + sin * 0.5, 0.5 + if up { -cos } else { cos } * 0.5),
+        ));
+    }
+    
+    let mut indices = Vec::new();
+    for s in 1..=segments {
+        if up {
+            indices.extend_from_slice(&[0, s, s + 1]);
+        } else {
+            indices.extend_from_slice(&[0, s + 1, s]);
+        }
+    }
+    
+    MeshData::new(vertices, indices)
+}
+
+fn bare_vertex(position: Vec3) -> Vertex {
+    Vertex::new(position, Vec3::zero(), Vec2::zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles(data: &MeshData) -> impl Iterator<Item = [Vertex; 3]> + '_ {
+        assert_eq!(data.topology, Topology::Triangles);
+        data.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]].map(|i| data.vertices[i as usize]))
+    }
+
+    fn face_normal([a, b, c]: &[Vertex; 3]) -> Vec3 {
+        glm::cross(b.v - a.v, c.v - a.v)
+    }
+
+    /// Valid, unit normals and counter clockwise winding agreeing with them.
+    fn assert_well_formed(data: &MeshData) {
+        data.validate().unwrap();
+        for vertex in data.vertices.iter() {
+            assert!((glm::length(vertex.vn) - 1.0).abs() < 1e-5, "normal {:?} isn't unit length", vertex.vn);
+        }
+        for triangle in triangles(data) {
+            let n = face_normal(&triangle);
+            assert!(glm::length(n) > 1e-8, "degenerate triangle {:?}", triangle.map(|v| v.v));
+            let vertex_normals = triangle[0].vn + triangle[1].vn + triangle[2].vn;
+            assert!(glm::dot(n, vertex_normals) > 0.0, "triangle {:?} is wound against its normals", triangle.map(|v| v.v));
+        }
+    }
+
+    /// Every face points away from the origin, which lies inside the shape.
+    fn assert_outward(data: &MeshData) {
+        for triangle in triangles(data) {
+            let centroid = (triangle[0].v + triangle[1].v + triangle[2].v) / 3.0;
+            assert!(glm::dot(face_normal(&triangle), centroid) > 0.0, "triangle {:?} faces inwards", triangle.map(|v| v.v));
+        }
+    }
+
+    fn counts(data: &MeshData) -> (usize, usize) {
+        (data.vertices.len(), data.indices.len())
+    }
+
+    #[test]
+    fn quad_and_cube() {
+        let quad = MeshData::quad();
+        assert_well_formed(&quad);
+        assert_eq!(counts(&quad), (4, 6));
+
+        let cube = MeshData::cube();
+        assert_well_formed(&cube);
+        assert_outward(&cube);
+        assert_eq!(counts(&cube), (24, 36));
+    }
+
+    #[test]
+    fn grids_clamp_their_segments() {
+        let grid = MeshData::grid(2.0, 1.0, 3, 2);
+        assert_well_formed(&grid);
+        assert_eq!(counts(&grid), (4 * 3, 3 * 2 * 6));
+        assert_eq!(grid.bounds().min, Vec3::new(-1.0, 0.0, -0.5));
+        assert_eq!(grid.bounds().max, Vec3::new(1.0, 0.0, 0.5));
+
+        assert_eq!(counts(&MeshData::grid(1.0, 1.0, 0, 0)), (4, 6));
+        assert_eq!(counts(&MeshData::plane(1.0, 1.0)), (4, 6));
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let (segments, rings) = (8, 5);
+        let sphere = MeshData::uv_sphere(2.0, segments, rings);
+        assert_well_formed(&sphere);
+        assert_outward(&sphere);
+        // one vertex per segment at the poles, the seam column is doubled elsewhere
+        assert_eq!(counts(&sphere), (2 * 8 + 4 * 9, 6 * segments as usize * (rings as usize - 1)));
+        for vertex in sphere.vertices.iter() {
+            assert!((glm::length(vertex.v) - 2.0).abs() < 1e-5);
+        }
+
+        let clamped = MeshData::uv_sphere(1.0, 0, 0);
+        assert_well_formed(&clamped);
+        assert_eq!(counts(&clamped), (2 * 3 + 4, 6 * 3));
+    }
+
+    #[test]
+    fn icosphere() {
+        for subdivisions in 0..3 {
+            let sphere = MeshData::icosphere(1.5, subdivisions);
+            assert_well_formed(&sphere);
+            assert_outward(&sphere);
+            assert_eq!(sphere.indices.len(), 20 * 4usize.pow(subdivisions) * 3);
+            for vertex in sphere.vertices.iter() {
+                assert!((glm::length(vertex.v) - 1.5).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn cylinder_and_cone() {
+        let cylinder = MeshData::cylinder(1.0, 2.0, 6);
+        assert_well_formed(&cylinder);
+        assert_outward(&cylinder);
+        // sides with a doubled seam column, then both caps with a center vertex
+        assert_eq!(counts(&cylinder), (2 * 7 + 2 * 8, 4 * 6 * 3));
+        assert_eq!(counts(&MeshData::cylinder(1.0, 2.0, 0)), (2 * 4 + 2 * 5, 4 * 3 * 3));
+
+        let cone = MeshData::cone(1.0, 2.0, 6);
+        assert_well_formed(&cone);
+        assert_outward(&cone);
+        assert_eq!(counts(&cone), (6 + 7 + 8, 2 * 6 * 3));
+        assert_eq!(counts(&MeshData::cone(1.0, 2.0, 1)), (3 + 4 + 5, 2 * 3 * 3));
+    }
+
+    #[test]
+    fn capsule() {
+        let capsule = MeshData::capsule(0.5, 1.0, 8, 3);
+        assert_well_formed(&capsule);
+        assert_outward(&capsule);
+        assert_eq!(counts(&capsule), (2 * 8 + 2 * 3 * 9, 4 * 3 * 8 * 3));
+        assert_eq!(capsule.bounds().max.y, 1.0);
+
+        assert_eq!(counts(&MeshData::capsule(0.5, 1.0, 0, 0)), (2 * 3 + 2 * 4, 4 * 3 * 3));
+    }
+
+    #[test]
+    fn torus() {
+        let (segments, rings) = (12, 6);
+        let torus = MeshData::torus(1.0, 0.25, segments, rings);
+        assert_well_formed(&torus);
+        // faces point away from the middle of the tube
+        for triangle in triangles(&torus) {
+            let centroid = (triangle[0].v + triangle[1].v + triangle[2].v) / 3.0;
+            let tube = glm::normalize(Vec3::new(centroid.x, 0.0, centroid.z));
+            assert!(glm::dot(face_normal(&triangle), centroid - tube) > 0.0);
+        }
+        assert_eq!(counts(&torus), (13 * 7, 2 * 12 * 6 * 3));
+
+        assert_eq!(counts(&MeshData::torus(1.0, 0.25, 0, 0)), (4 * 4, 2 * 3 * 3 * 3));
+    }
+
+    #[test]
+    fn debug_geometry() {
+        let points = [Vec3::zero(), Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)];
+        assert_eq!(MeshData::points(&points).topology, Topology::Points);
+        assert_eq!(MeshData::polyline(&points, true).topology, Topology::LineLoop);
+        assert_eq!(MeshData::polyline(&points, false).topology, Topology::LineStrip);
+
+        let grid = MeshData::line_grid(2.0, 4);
+        assert_eq!(grid.topology, Topology::Lines);
+        assert_eq!(counts(&grid), (4 * 5, 0));
+        assert_eq!(MeshData::line_grid(2.0, 0).vertices.len(), 4 * 2);
+
+        let axes = MeshData::axes(2.0);
+        assert_eq!(axes.vertices.len(), 6);
+        assert_eq!(axes.vertices[5].v, Vec3::new(0.0, 0.0, 2.0));
+    }
+}
@@ -0,0 +1,153 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use crate::renderer::gl_caps;
+
+/// Identifies cache files, followed by the binary format and the binary.
+const MAGIC: &[u8; 4] = b"GLPB";
+
+/// Linked program binaries on disk, keyed by a hash of the expanded stage
+/// sources and the driver. Entries the driver rejects are recompiled and
+/// overwritten, so nothing has to be invalidated by hand.
+#[derive(Clone, Debug)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl Default for ProgramCache {
+    fn default() -> Self {
+        Self::new(std::env::temp_dir().join("gl_rust_program_cache"))
+    }
+}
+
+impl ProgramCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Cache key for a program made of `(stage type, source)` pairs. GLSL sources
+    /// have to be fully expanded, with includes and defines.
+    pub fn key<'a>(stages: impl IntoIterator<Item = (u32, &'a [u8])>) -> u64 {
+        static DRIVER: OnceLock<String> = OnceLock::new();
+        let mut hash = fnv1a(FNV_OFFSET, DRIVER.get_or_init(gl_caps::driver).as_bytes());
+        for (stage, source) in stages {
+            hash = fnv1a(hash, &stage.to_le_bytes());
+            hash = fnv1a(hash, &(source.len() as u64).to_le_bytes());
+            hash = fnv1a(hash, source);
+        }
+        hash
+    }
+
+    /// Creates a program from the cached binary. `None` if there is no entry
+    /// or the driver doesn't accept it anymore, rejected entries are deleted.
+    pub fn load(&self, key: u64) -> Option<u32> {
+        if !gl_caps::get().program_binary {
+            return None;
+        }
+        let path = self.path(key);
+        let data = std::fs::read(&path).ok()?;
+        let program = create_program(&data);
+        if program.is_none() {
+            // stale or broken, so it isn't retried on every start
+            let _ = std::fs::remove_file(&path);
+        }
+        program
+    }
+
+    /// Writes the binary of a linked program. Failures only cost a recompile
+    /// next time, so they are ignored.
+    pub fn store(&self, key: u64, program: u32) {
+        if !gl_caps::get().program_binary {
+            return;
+        }
+        let mut length = 0;
+        unsafe {
+            gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+        if length <= 0 {
+            return;
+        }
+
+        let mut binary = vec![0u8; length as usize];
+        let mut format = 0;
+        let mut written = 0;
+        unsafe {
+            gl::GetProgramBinary(program, length, &mut written, &mut format, binary.as_mut_ptr() as *mut _);
+        }
+        binary.truncate(written.max(0) as usize);
+
+        let mut data = Vec::with_capacity(MAGIC.len() + 4 + binary.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+
+        // write next to the entry and rename, so a crash never leaves half a file
+        let path = self.path(key);
+        let temp = path.with_extension("tmp");
+        let _ = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&temp, &data))
+            .and_then(|_| std::fs::rename(&temp, &path));
+    }
+
+    /// Deletes all cached binaries.
+    pub fn clear(&self) -> std::io::Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+fn create_program(data: &[u8]) -> Option<u32> {
+    let rest = data.strip_prefix(MAGIC)?;
+    let (format, binary) = rest.split_at_checked(4)?;
+    let format = u32::from_le_bytes(format.try_into().ok()?);
+    // formats the driver dropped, e.g. after an update, raise GL_INVALID_ENUM
+    if !binary_formats().contains(&format) {
+        return None;
+    }
+
+    unsafe {
+        let program = gl::CreateProgram();
+        gl::ProgramBinary(program, format, binary.as_ptr() as *const _, binary.len() as i32);
+        let mut success = 0;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+        if success != 1 {
+            gl::DeleteProgram(program);
+            // a rejected binary may leave an error behind, which must not be
+            // reported by the next unrelated `check_gl`
+            while gl::GetError() != gl::NO_ERROR {}
+            return None;
+        }
+        Some(program)
+    }
+}
+
+/// The program binary formats the driver accepts.
+fn binary_formats() -> Vec<u32> {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut count);
+    }
+    let mut formats = vec![0; count.max(0) as usize];
+    if !formats.is_empty() {
+        unsafe {
+            gl::GetIntegerv(gl::PROGRAM_BINARY_FORMATS, formats.as_mut_ptr());
+        }
+    }
+    formats.into_iter().map(|format| format as u32).collect()
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
@@ -0,0 +1,352 @@
+use std::fmt;
+use std::ptr::null_mut;
+use crate::renderer::error::RendererError;
+use crate::renderer::vertex_layout::{AttributeKind, VertexAttribute};
+
+/// GLSL type of a uniform, block member or vertex input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GlslType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    BVec2,
+    BVec3,
+    BVec4,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DArray,
+    Sampler2DShadow,
+    ISampler2D,
+    USampler2D,
+    Image2D,
+    /// Any other type, with its GL enum.
+    Other(u32),
+}
+
+impl GlslType {
+    pub fn from_gl(ty: u32) -> Self {
+        match ty {
+            gl::FLOAT => GlslType::Float,
+            gl::FLOAT_VEC2 => GlslType::Vec2,
+            gl::FLOAT_VEC3 => GlslType::Vec3,
+            gl::FLOAT_VEC4 => GlslType::Vec4,
+            gl::INT => GlslType::Int,
+            gl::INT_VEC2 => GlslType::IVec2,
+            gl::INT_VEC3 => GlslType::IVec3,
+            gl::INT_VEC4 => GlslType::IVec4,
+            gl::UNSIGNED_INT => GlslType::UInt,
+            gl::UNSIGNED_INT_VEC2 => GlslType::UVec2,
+            gl::UNSIGNED_INT_VEC3 => GlslType::UVec3,
+            gl::UNSIGNED_INT_VEC4 => GlslType::UVec4,
+            gl::BOOL => GlslType::Bool,
+            gl::BOOL_VEC2 => GlslType::BVec2,
+            gl::BOOL_VEC3 => GlslType::BVec3,
+            gl::BOOL_VEC4 => GlslType::BVec4,
+            gl::FLOAT_MAT2 => GlslType::Mat2,
+            gl::FLOAT_MAT3 => GlslType::Mat3,
+            gl::FLOAT_MAT4 => GlslType::Mat4,
+            gl::SAMPLER_2D => GlslType::Sampler2D,
+            gl::SAMPLER_3D => GlslType::Sampler3D,
+            gl::SAMPLER_CUBE => GlslType::SamplerCube,
+            gl::SAMPLER_2D_ARRAY => GlslType::Sampler2DArray,
+            gl::SAMPLER_2D_SHADOW => GlslType::Sampler2DShadow,
+            gl::INT_SAMPLER_2D => GlslType::ISampler2D,
+            gl::UNSIGNED_INT_SAMPLER_2D => GlslType::USampler2D,
+            gl::IMAGE_2D => GlslType::Image2D,
+            other => GlslType::Other(other),
+        }
+    }
+
+    pub fn is_sampler(&self) -> bool {
+        matches!(self,
+            GlslType::Sampler2D | GlslType::Sampler3D | GlslType::SamplerCube | GlslType::Sampler2DArray
+            | GlslType::Sampler2DShadow | GlslType::ISampler2D | GlslType::USampler2D)
+    }
+
+    /// Samplers and images, set with the texture unit or image unit as an `int`.
+    pub fn is_opaque(&self) -> bool {
+        self.is_sampler() || *self == GlslType::Image2D
+    }
+
+    /// `int`/`uint` scalars and vectors, which need integer vertex attributes.
+    pub fn is_integer(&self) -> bool {
+        matches!(self,
+            GlslType::Int | GlslType::IVec2 | GlslType::IVec3 | GlslType::IVec4
+            | GlslType::UInt | GlslType::UVec2 | GlslType::UVec3 | GlslType::UVec4)
+    }
+
+    /// Vertex attribute locations taken by an input of this type.
+    pub fn columns(&self) -> u32 {
+        match self {
+            GlslType::Mat2 => 2,
+            GlslType::Mat3 => 3,
+            GlslType::Mat4 => 4,
+            _ => 1,
+        }
+    }
+
+    pub fn glsl_name(&self) -> &'static str {
+        match self {
+            GlslType::Float => "float",
+            GlslType::Vec2 => "vec2",
+            GlslType::Vec3 => "vec3",
+            GlslType::Vec4 => "vec4",
+            GlslType::Int => "int",
+            GlslType::IVec2 => "ivec2",
+            GlslType::IVec3 => "ivec3",
+            GlslType::IVec4 => "ivec4",
+            GlslType::UInt => "uint",
+            GlslType::UVec2 => "uvec2",
+            GlslType::UVec3 => "uvec3",
+            GlslType::UVec4 => "uvec4",
+            GlslType::Bool => "bool",
+            GlslType::BVec2 => "bvec2",
+            GlslType::BVec3 => "bvec3",
+            GlslType::BVec4 => "bvec4",
+            GlslType::Mat2 => "mat2",
+            GlslType::Mat3 => "mat3",
+            GlslType::Mat4 => "mat4",
+            GlslType::Sampler2D => "sampler2D",
+            GlslType::Sampler3D => "sampler3D",
+            GlslType::SamplerCube => "samplerCube",
+            GlslType::Sampler2DArray => "sampler2DArray",
+            GlslType::Sampler2DShadow => "sampler2DShadow",
+            GlslType::ISampler2D => "isampler2D",
+            GlslType::USampler2D => "usampler2D",
+            GlslType::Image2D => "image2D",
+            GlslType::Other(_) => "<other>",
+        }
+    }
+}
+
+impl fmt::Display for GlslType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlslType::Other(ty) => write!(f, "<type 0x{:x}>", ty),
+            _ => f.write_str(self.glsl_name()),
+        }
+    }
+}
+
+/// A uniform in the default block, i.e. one set with `glUniform*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniformInfo {
+    /// Name without a trailing `[0]` for arrays.
+    pub name: String,
+    pub ty: GlslType,
+    /// 1 for non-arrays.
+    pub array_size: i32,
+    pub location: i32,
+}
+
+/// A uniform or shader storage block and the std140/std430 layout of its members.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockInfo {
+    pub name: String,
+    pub binding: u32,
+    /// Minimum buffer size in bytes.
+    pub size: usize,
+    /// Sorted by offset.
+    pub members: Vec<BlockMember>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockMember {
+    pub name: String,
+    pub ty: GlslType,
+    pub offset: usize,
+    pub array_size: i32,
+    pub array_stride: usize,
+    pub matrix_stride: usize,
+}
+
+/// A vertex shader input with an explicit or assigned location.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexInput {
+    pub name: String,
+    pub ty: GlslType,
+    pub location: u32,
+}
+
+/// Everything a linked program expects from the application, queried once after linking.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgramReflection {
+    pub uniforms: Vec<UniformInfo>,
+    pub uniform_blocks: Vec<BlockInfo>,
+    pub storage_blocks: Vec<BlockInfo>,
+    /// Sorted by location, empty for compute programs.
+    pub inputs: Vec<VertexInput>,
+}
+
+impl ProgramReflection {
+    /// Queries the active resources of a linked program.
+    pub fn reflect(program: u32) -> Self {
+        let uniforms = (0..resource_count(program, gl::UNIFORM))
+            .filter_map(|index| {
+                let [ty, array_size, location, block] = resource_props(program, gl::UNIFORM, index, [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION, gl::BLOCK_INDEX]);
+                // members of blocks are listed with their block
+                (block == -1).then(|| UniformInfo {
+                    name: resource_name(program, gl::UNIFORM, index),
+                    ty: GlslType::from_gl(ty as u32),
+                    array_size,
+                    location,
+                })
+            })
+            .collect();
+
+        let mut inputs: Vec<VertexInput> = (0..resource_count(program, gl::PROGRAM_INPUT))
+            .filter_map(|index| {
+                let [ty, location] = resource_props(program, gl::PROGRAM_INPUT, index, [gl::TYPE, gl::LOCATION]);
+                // built-ins like gl_VertexID have no location
+                (location >= 0).then(|| VertexInput {
+                    name: resource_name(program, gl::PROGRAM_INPUT, index),
+                    ty: GlslType::from_gl(ty as u32),
+                    location: location as u32,
+                })
+            })
+            .collect();
+        inputs.sort_by_key(|input| input.location);
+
+        Self {
+            uniforms,
+            uniform_blocks: blocks(program, gl::UNIFORM_BLOCK, gl::UNIFORM),
+            storage_blocks: blocks(program, gl::SHADER_STORAGE_BLOCK, gl::BUFFER_VARIABLE),
+            inputs,
+        }
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&UniformInfo> {
+        self.uniforms.iter().find(|uniform| uniform.name == name)
+    }
+
+    /// The uniform `location` belongs to, also for locations of array elements.
+    pub fn uniform_at(&self, location: i32) -> Option<&UniformInfo> {
+        self.uniforms.iter().find(|uniform| {
+            uniform.location >= 0 && location >= uniform.location && location < uniform.location + uniform.array_size
+        })
+    }
+
+    pub fn uniform_block(&self, name: &str) -> Option<&BlockInfo> {
+        self.uniform_blocks.iter().find(|block| block.name == name)
+    }
+
+    pub fn storage_block(&self, name: &str) -> Option<&BlockInfo> {
+        self.storage_blocks.iter().find(|block| block.name == name)
+    }
+
+    pub fn input(&self, name: &str) -> Option<&VertexInput> {
+        self.inputs.iter().find(|input| input.name == name)
+    }
+
+    /// Checks that `attributes` supply every vertex input with a matching kind:
+    /// integer inputs need integer attributes and float inputs float or normalized ones.
+    pub fn check_vertex_layout(&self, attributes: &[VertexAttribute]) -> Result<(), RendererError> {
+        for input in self.inputs.iter() {
+            for location in input.location..input.location + input.ty.columns() {
+                let attribute = attributes.iter()
+                    .find(|a| location >= a.location && location < a.location + a.columns)
+                    .ok_or_else(|| RendererError::Layout(format!("Input '{}' ({} at location {}) is not supplied by the vertex layout", input.name, input.ty, location)))?;
+                let integer = attribute.kind == AttributeKind::Integer;
+                if integer != input.ty.is_integer() {
+                    return Err(RendererError::Layout(format!("Input '{}' at location {} is {} but the vertex layout supplies {:?} data",
+                        input.name, location, input.ty, attribute.kind)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProgramReflection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for input in self.inputs.iter() {
+            writeln!(f, "in (location = {}) {} {}", input.location, input.ty, input.name)?;
+        }
+        for uniform in self.uniforms.iter() {
+            let array = if uniform.array_size > 1 { format!("[{}]", uniform.array_size) } else { String::new() };
+            writeln!(f, "uniform (location = {}) {} {}{}", uniform.location, uniform.ty, uniform.name, array)?;
+        }
+        for (kind, blocks) in [("uniform", &self.uniform_blocks), ("buffer", &self.storage_blocks)] {
+            for block in blocks.iter() {
+                writeln!(f, "{} (binding = {}) {}, {} bytes", kind, block.binding, block.name, block.size)?;
+                for member in block.members.iter() {
+                    writeln!(f, "    {:>4}: {} {}", member.offset, member.ty, member.name)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn resource_count(program: u32, interface: u32) -> u32 {
+    let mut count = 0;
+    unsafe {
+        gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count);
+    }
+    count.max(0) as u32
+}
+
+fn resource_props<const N: usize>(program: u32, interface: u32, index: u32, props: [u32; N]) -> [i32; N] {
+    let mut values = [0; N];
+    unsafe {
+        gl::GetProgramResourceiv(program, interface, index, N as i32, props.as_ptr(), N as i32, null_mut(), values.as_mut_ptr());
+    }
+    values
+}
+
+fn resource_name(program: u32, interface: u32, index: u32) -> String {
+    let [length] = resource_props(program, interface, index, [gl::NAME_LENGTH]);
+    let mut name = vec![0u8; length.max(1) as usize];
+    unsafe {
+        gl::GetProgramResourceName(program, interface, index, name.len() as i32, null_mut(), name.as_mut_ptr() as *mut _);
+    }
+    let name = String::from_utf8_lossy(&name);
+    let name = name.trim_end_matches('\0');
+    name.strip_suffix("[0]").unwrap_or(name).to_string()
+}
+
+fn blocks(program: u32, block_interface: u32, member_interface: u32) -> Vec<BlockInfo> {
+    (0..resource_count(program, block_interface)).map(|index| {
+        let [binding, size, count] = resource_props(program, block_interface, index, [gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE, gl::NUM_ACTIVE_VARIABLES]);
+        let mut variables = vec![0i32; count.max(0) as usize];
+        unsafe {
+            gl::GetProgramResourceiv(program, block_interface, index, 1, &gl::ACTIVE_VARIABLES, variables.len() as i32, null_mut(), variables.as_mut_ptr());
+        }
+
+        let mut members: Vec<BlockMember> = variables.iter().map(|&variable| {
+            let variable = variable as u32;
+            let [ty, offset, array_size, array_stride, matrix_stride] = resource_props(program, member_interface, variable,
+                [gl::TYPE, gl::OFFSET, gl::ARRAY_SIZE, gl::ARRAY_STRIDE, gl::MATRIX_STRIDE]);
+            BlockMember {
+                name: resource_name(program, member_interface, variable),
+                ty: GlslType::from_gl(ty as u32),
+                offset: offset as usize,
+                array_size,
+                array_stride: array_stride as usize,
+                matrix_stride: matrix_stride as usize,
+            }
+        }).collect();
+        members.sort_by_key(|member| member.offset);
+
+        BlockInfo {
+            name: resource_name(program, block_interface, index),
+            binding: binding as u32,
+            size: size as usize,
+            members,
+        }
+    }).collect()
+}
@@ -0,0 +1,261 @@
+use glm::Vec4;
+use num_traits::Zero;
+use crate::renderer::gl_caps;
+
+/// `GL_TEXTURE_MAX_ANISOTROPY`, core in 4.6 and not covered by the `gl` bindings.
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// How mip levels are picked and blended. `None` samples the base level only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+    None,
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+    MirrorClampToEdge,
+}
+
+/// Comparison of depth textures against the reference coordinate, e.g. for
+/// `sampler2DShadow`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+    Always,
+}
+
+/// How a texture is sampled. Either set on the texture itself, see
+/// `Texture::with_sampler`, or on a `Sampler` object that overrides it per unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDesc {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mip_filter: MipFilter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    /// Used by `Wrap::ClampToBorder`.
+    pub border_color: Vec4,
+    /// Maximum anisotropy, 1 disables it. Clamped to what the driver supports.
+    pub anisotropy: f32,
+    pub lod_bias: f32,
+    pub compare: Option<CompareFunc>,
+}
+
+/// Trilinear filtering with repeating coordinates.
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mip_filter: MipFilter::Linear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            wrap_r: Wrap::Repeat,
+            border_color: Vec4::zero(),
+            anisotropy: 1.0,
+            lod_bias: 0.0,
+            compare: None,
+        }
+    }
+}
+
+impl SamplerDesc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Blocky magnification without mipmaps, e.g. for pixel art or lookup tables.
+    pub fn nearest() -> Self {
+        Self::default().with_filter(Filter::Nearest).with_mip_filter(MipFilter::None)
+    }
+
+    /// Sets both the minification and the magnification filter.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn with_mip_filter(mut self, mip_filter: MipFilter) -> Self {
+        self.mip_filter = mip_filter;
+        self
+    }
+
+    /// Sets the wrap mode of all three coordinates.
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    pub fn with_border_color(mut self, color: Vec4) -> Self {
+        self.border_color = color;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn with_lod_bias(mut self, lod_bias: f32) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    pub fn with_compare(mut self, compare: CompareFunc) -> Self {
+        self.compare = Some(compare);
+        self
+    }
+
+    /// True if textures sampled like this need mip levels.
+    pub fn uses_mipmaps(&self) -> bool {
+        self.mip_filter != MipFilter::None
+    }
+
+    /// Sets every parameter through the given `glTexParameter*`-like functions,
+    /// so textures and sampler objects share the same code.
+    pub(crate) fn apply(&self, set_int: impl Fn(u32, i32), set_float: impl Fn(u32, f32), set_floats: impl Fn(u32, &[f32; 4])) {
+        set_int(gl::TEXTURE_MIN_FILTER, self.gl_min_filter() as i32);
+        set_int(gl::TEXTURE_MAG_FILTER, gl_filter(self.mag_filter) as i32);
+        set_int(gl::TEXTURE_WRAP_S, gl_wrap(self.wrap_s) as i32);
+        set_int(gl::TEXTURE_WRAP_T, gl_wrap(self.wrap_t) as i32);
+        set_int(gl::TEXTURE_WRAP_R, gl_wrap(self.wrap_r) as i32);
+        set_floats(gl::TEXTURE_BORDER_COLOR, &[self.border_color.x, self.border_color.y, self.border_color.z, self.border_color.w]);
+        set_float(gl::TEXTURE_LOD_BIAS, self.lod_bias);
+        match self.compare {
+            Some(func) => {
+                set_int(gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+                set_int(gl::TEXTURE_COMPARE_FUNC, gl_compare(func) as i32);
+            }
+            None => set_int(gl::TEXTURE_COMPARE_MODE, gl::NONE as i32),
+        }
+        let max_anisotropy = gl_caps::get().max_anisotropy;
+        if max_anisotropy > 1.0 {
+            set_float(TEXTURE_MAX_ANISOTROPY, self.anisotropy.clamp(1.0, max_anisotropy));
+        }
+    }
+
+    fn gl_min_filter(&self) -> u32 {
+        match (self.min_filter, self.mip_filter) {
+            (filter, MipFilter::None) => gl_filter(filter),
+            (Filter::Nearest, MipFilter::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipFilter::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipFilter::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipFilter::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+fn gl_filter(filter: Filter) -> u32 {
+    match filter {
+        Filter::Nearest => gl::NEAREST,
+        Filter::Linear => gl::LINEAR,
+    }
+}
+
+fn gl_wrap(wrap: Wrap) -> u32 {
+    match wrap {
+        Wrap::Repeat => gl::REPEAT,
+        Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+        Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+        Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        Wrap::MirrorClampToEdge => gl::MIRROR_CLAMP_TO_EDGE,
+    }
+}
+
+fn gl_compare(func: CompareFunc) -> u32 {
+    match func {
+        CompareFunc::Never => gl::NEVER,
+        CompareFunc::Less => gl::LESS,
+        CompareFunc::LessEqual => gl::LEQUAL,
+        CompareFunc::Equal => gl::EQUAL,
+        CompareFunc::NotEqual => gl::NOTEQUAL,
+        CompareFunc::GreaterEqual => gl::GEQUAL,
+        CompareFunc::Greater => gl::GREATER,
+        CompareFunc::Always => gl::ALWAYS,
+    }
+}
+
+/// A GL sampler object. Bound to a unit it replaces the sampling parameters
+/// of whatever texture is bound there, so one texture can be sampled in
+/// different ways.
+pub struct Sampler {
+    id: u32,
+    desc: SamplerDesc,
+}
+
+impl Sampler {
+    pub fn new(desc: SamplerDesc) -> Self {
+        let mut id = 0;
+        unsafe {
+            if gl_caps::dsa() {
+                gl::CreateSamplers(1, &mut id);
+            } else {
+                gl::GenSamplers(1, &mut id);
+            }
+        }
+        let mut sampler = Self { id, desc };
+        sampler.set_desc(desc);
+        sampler
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn desc(&self) -> &SamplerDesc {
+        &self.desc
+    }
+
+    pub fn set_desc(&mut self, desc: SamplerDesc) {
+        self.desc = desc;
+        let id = self.id;
+        desc.apply(
+            |name, value| unsafe { gl::SamplerParameteri(id, name, value) },
+            |name, value| unsafe { gl::SamplerParameterf(id, name, value) },
+            |name, values| unsafe { gl::SamplerParameterfv(id, name, values.as_ptr()) },
+        );
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, self.id);
+        }
+    }
+
+    /// Goes back to the parameters of the texture bound to `unit`.
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, 0);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use glm::{Mat4, Vec2, Vec3, Vec4};
+use image::{DynamicImage, ImageBuffer};
+use num_traits::{One, Zero};
+use crate::renderer::buffer::BufferUsage;
+use crate::renderer::error::RendererError;
+use crate::renderer::material::Material;
+use crate::renderer::mesh::{Mesh, Topology};
+use crate::renderer::mesh_data::MeshData;
+use crate::renderer::model::{Model, Transform};
+use crate::renderer::sampler::{Filter, MipFilter, SamplerDesc, Wrap};
+use crate::renderer::shader::Shader;
+use crate::renderer::texture::Texture;
+use crate::renderer::texture_format::{ColorSpace, TextureFormat};
+use crate::renderer::vertex::{TangentVertex, Vertex};
+
+/// A node of an imported scene. Models are positioned relative to the node,
+/// which itself is positioned relative to its parent.
+pub struct SceneNode {
+    pub name: Option<String>,
+    pub transform: Transform,
+    pub models: Vec<Model>,
+    pub children: Vec<SceneNode>,
+}
+
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+}
+
+impl Scene {
+    /// Imports the default scene (or the first one) of a .gltf or .glb file.
+    /// Every primitive becomes a `Model` drawn with `shader`.
+    pub fn from_gltf(path: &Path, shader: Rc<RefCell<Shader>>) -> Result<Self, RendererError> {
+        let (document, buffers, images) = gltf::import(path)
+            .map_err(|e| RendererError::asset(path, None, e.to_string()))?;
+
+        let scene = document.default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| RendererError::asset(path, None, "contains no scene"))?;
+
+        let mut importer = GltfImporter {
+            path,
+            buffers: &buffers,
+            images: &images,
+            shader,
+            meshes: HashMap::new(),
+            textures: HashMap::new(),
+        };
+
+        let nodes = scene.nodes()
+            .map(|node| importer.import_node(&node))
+            .collect::<Result<Vec<_>, RendererError>>()?;
+
+        Ok(Self { nodes })
+    }
+
+    /// The first node called `name`, searched depth first.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        self.nodes.iter_mut().find_map(|node| node.find_mut(name))
+    }
+
+    pub fn render(&self, pv_mat: Mat4) -> Result<(), RendererError> {
+        for node in self.nodes.iter() {
+            node.render(pv_mat, Mat4::one())?;
+        }
+        Ok(())
+    }
+}
+
+impl SceneNode {
+    /// This node or the first descendant called `name`.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        if self.name.as_deref() == Some(name) {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|child| child.find_mut(name))
+    }
+
+    pub fn render(&self, pv_mat: Mat4, parent: Mat4) -> Result<(), RendererError> {
+        let world = parent * self.transform.model_matrix();
+        for model in self.models.iter() {
+            model.render(pv_mat * world)?;
+        }
+        for child in self.children.iter() {
+            child.render(pv_mat, world)?;
+        }
+        Ok(())
+    }
+}
+
+struct GltfImporter<'a> {
+    path: &'a Path,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    shader: Rc<RefCell<Shader>>,
+
+    // meshes and textures can be referenced several times, upload them once
+    meshes: HashMap<(usize, usize), Rc<RefCell<Mesh>>>,
+    textures: HashMap<(usize, ColorSpace), Rc<Texture>>,
+}
+
+impl GltfImporter<'_> {
+    fn error(&self, message: String) -> RendererError {
+        RendererError::asset(self.path, None, message)
+    }
+
+    fn import_node(&mut self, node: &gltf::Node) -> Result<SceneNode, RendererError> {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let transform = Transform::new(
+            Vec3::new(translation[0], translation[1], translation[2]),
+            Vec3::new(scale[0], scale[1], scale[2]),
+            euler_from_quat(rotation),
+        );
+
+        let mut models = Vec::new();
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                let material = self.import_material(&primitive.material())?;
+                let gpu_mesh = self.import_primitive(&mesh, &primitive, material.bump_map.is_some())?;
+                models.push(Model::with_material(
+                    gpu_mesh,
+                    self.shader.clone(),
+                    Transform::new(Vec3::zero(), Vec3::one(), Vec3::zero()),
+                    material,
+                ));
+            }
+        }
+
+        let children = node.children()
+            .map(|child| self.import_node(&child))
+            .collect::<Result<Vec<_>, RendererError>>()?;
+
+        Ok(SceneNode {
+            name: node.name().map(String::from),
+            transform,
+            models,
+            children,
+        })
+    }
+
+    fn import_primitive(&mut self, mesh: &gltf::Mesh, primitive: &gltf::Primitive, needs_tangents: bool) -> Result<Rc<RefCell<Mesh>>, RendererError> {
+        let key = (mesh.index(), primitive.index());
+        if let Some(gpu_mesh) = self.meshes.get(&key) {
+            return Ok(gpu_mesh.clone());
+        }
+
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+        let positions: Vec<[f32; 3]> = reader.read_positions()
+            .ok_or_else(|| self.error(format!("mesh {} primitive {} has no positions", mesh.index(), primitive.index())))?
+            .collect();
+        let normals: Vec<[f32; 3]> = reader.read_normals()
+            .map(|normals| normals.collect())
+            .unwrap_or_default();
+        let uvs: Vec<[f32; 2]> = reader.read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().collect())
+            .unwrap_or_default();
+        let tangents: Vec<[f32; 4]> = reader.read_tangents()
+            .map(|tangents| tangents.collect())
+            .unwrap_or_default();
+
+        let vertices: Vec<Vertex> = positions.iter().enumerate().map(|(i, p)| Vertex::new(
+            Vec3::new(p[0], p[1], p[2]),
+            normals.get(i).map_or(Vec3::zero(), |n| Vec3::new(n[0], n[1], n[2])),
+            uvs.get(i).map_or(Vec2::zero(), |t| Vec2::new(t[0], t[1])),
+        )).collect();
+
+        // without indices the vertices are drawn in order
+        let indices: Vec<u32> = reader.read_indices()
+            .map(|indices| indices.into_u32().collect())
+            .unwrap_or_default();
+        let mut data = MeshData::with_topology(vertices, indices, topology_from_mode(primitive.mode()));
+        data.validate()
+            .map_err(|e| self.error(format!("mesh {} primitive {}: {}", mesh.index(), primitive.index(), e)))?;
+
+        // the spec asks for flat normals when they are missing, and for
+        // ignoring the tangents then
+        if normals.is_empty() {
+            data.compute_flat_normals();
+        }
+        let mesh = if !needs_tangents {
+            data.upload()
+        } else if normals.is_empty() || tangents.len() != data.vertices.len() {
+            data.upload_with_tangents()?
+        } else {
+            let vertices: Vec<TangentVertex> = data.vertices.iter().zip(tangents.iter())
+                .map(|(&vertex, t)| TangentVertex::new(vertex, Vec4::new(t[0], t[1], t[2], t[3])))
+                .collect();
+            Mesh::from_vertices(&vertices, &data.indices, data.topology, BufferUsage::Static)
+        };
+
+        let gpu_mesh = Rc::new(RefCell::new(mesh));
+        self.meshes.insert(key, gpu_mesh.clone());
+        Ok(gpu_mesh)
+    }
+
+    fn import_material(&mut self, material: &gltf::Material) -> Result<Material, RendererError> {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color = pbr.base_color_factor();
+        let emissive = material.emissive_factor();
+
+        Ok(Material {
+            name: material.name().unwrap_or("default").to_string(),
+            diffuse: Vec3::new(base_color[0], base_color[1], base_color[2]),
+            opacity: base_color[3],
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emissive: Vec3::new(emissive[0], emissive[1], emissive[2]),
+            alpha_cutoff: match material.alpha_mode() {
+                gltf::material::AlphaMode::Mask => Some(material.alpha_cutoff().unwrap_or(0.5)),
+                _ => None,
+            },
+            diffuse_map: self.import_texture(pbr.base_color_texture().map(|info| info.texture()), ColorSpace::Srgb)?,
+            metallic_roughness_map: self.import_texture(pbr.metallic_roughness_texture().map(|info| info.texture()), ColorSpace::Linear)?,
+            bump_map: self.import_texture(material.normal_texture().map(|info| info.texture()), ColorSpace::Linear)?,
+            emissive_map: self.import_texture(material.emissive_texture().map(|info| info.texture()), ColorSpace::Srgb)?,
+            ..Material::default()
+        })
+    }
+
+    fn import_texture(&mut self, texture: Option<gltf::Texture>, color_space: ColorSpace) -> Result<Option<Rc<Texture>>, RendererError> {
+        let Some(texture) = texture else { return Ok(None) };
+
+        // textures pair an image with a sampler, so the same image can be uploaded twice
+        let key = (texture.index(), color_space);
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(Some(texture.clone()));
+        }
+
+        let index = texture.source().index();
+        let data = self.images.get(index)
+            .ok_or_else(|| self.error(format!("image {index} is missing")))?;
+        let image = to_dynamic_image(data)
+            .ok_or_else(|| self.error(format!("image {index} has an unsupported format {:?}", data.format)))?;
+
+        // glTF uv coordinates start at the top left, so the image isn't flipped
+        let format = TextureFormat::infer(&image, color_space);
+        let gpu_texture = Rc::new(Texture::from_image_with_format(&image, format, sampler_from_gltf(&texture.sampler()))?);
+        self.textures.insert(key, gpu_texture.clone());
+        Ok(Some(gpu_texture))
+    }
+}
+
+fn topology_from_mode(mode: gltf::mesh::Mode) -> Topology {
+    use gltf::mesh::Mode;
+
+    match mode {
+        Mode::Points => Topology::Points,
+        Mode::Lines => Topology::Lines,
+        Mode::LineLoop => Topology::LineLoop,
+        Mode::LineStrip => Topology::LineStrip,
+        Mode::Triangles => Topology::Triangles,
+        Mode::TriangleStrip => Topology::TriangleStrip,
+        Mode::TriangleFan => Topology::TriangleFan,
+    }
+}
+
+/// Missing filters keep the defaults, glTF leaves them up to the renderer.
+fn sampler_from_gltf(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let wrap = |mode| match mode {
+        WrappingMode::ClampToEdge => Wrap::ClampToEdge,
+        WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+        WrappingMode::Repeat => Wrap::Repeat,
+    };
+
+    let mut desc = SamplerDesc {
+        wrap_s: wrap(sampler.wrap_s()),
+        wrap_t: wrap(sampler.wrap_t()),
+        ..SamplerDesc::default()
+    };
+    if let Some(mag_filter) = sampler.mag_filter() {
+        desc.mag_filter = match mag_filter {
+            MagFilter::Nearest => Filter::Nearest,
+            MagFilter::Linear => Filter::Linear,
+        };
+    }
+    if let Some(min_filter) = sampler.min_filter() {
+        (desc.min_filter, desc.mip_filter) = match min_filter {
+            MinFilter::Nearest => (Filter::Nearest, MipFilter::None),
+            MinFilter::Linear => (Filter::Linear, MipFilter::None),
+            MinFilter::NearestMipmapNearest => (Filter::Nearest, MipFilter::Nearest),
+            MinFilter::LinearMipmapNearest => (Filter::Linear, MipFilter::Nearest),
+            MinFilter::NearestMipmapLinear => (Filter::Nearest, MipFilter::Linear),
+            MinFilter::LinearMipmapLinear => (Filter::Linear, MipFilter::Linear),
+        };
+    }
+    desc
+}
+
+fn to_dynamic_image(data: &gltf::image::Data) -> Option<DynamicImage> {
+    use gltf::image::Format;
+
+    let (w, h) = (data.width, data.height);
+    let bytes = || data.pixels.clone();
+    let wide = || data.pixels.chunks_exact(2).map(|c| u16::from_ne_bytes([c[0], c[1]])).collect::<Vec<_>>();
+    let float = || data.pixels.chunks_exact(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect::<Vec<_>>();
+
+    match data.format {
+        Format::R8 => ImageBuffer::from_raw(w, h, bytes()).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(w, h, bytes()).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(w, h, bytes()).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => ImageBuffer::from_raw(w, h, bytes()).map(DynamicImage::ImageRgba8),
+        Format::R16 => ImageBuffer::from_raw(w, h, wide()).map(DynamicImage::ImageLuma16),
+        Format::R16G16 => ImageBuffer::from_raw(w, h, wide()).map(DynamicImage::ImageLumaA16),
+        Format::R16G16B16 => ImageBuffer::from_raw(w, h, wide()).map(DynamicImage::ImageRgb16),
+        Format::R16G16B16A16 => ImageBuffer::from_raw(w, h, wide()).map(DynamicImage::ImageRgba16),
+        Format::R32G32B32FLOAT => ImageBuffer::from_raw(w, h, float()).map(DynamicImage::ImageRgb32F),
+        Format::R32G32B32A32FLOAT => ImageBuffer::from_raw(w, h, float()).map(DynamicImage::ImageRgba32F),
+    }
+}
+
+/// Converts a glTF `[x, y, z, w]` rotation into the euler angles used by
+/// `Transform`, which rotates around x, then y, then z (`Rx * Ry * Rz`).
+fn euler_from_quat(q: [f32; 4]) -> Vec3 {
+    let [x, y, z, w] = q;
+
+    let r00 = 1.0 - 2.0 * (y * y + z * z);
+    let r01 = 2.0 * (x * y - z * w);
+    let r02 = 2.0 * (x * z + y * w);
+    let r11 = 1.0 - 2.0 * (x * x + z * z);
+    let r12 = 2.0 * (y * z - x * w);
+    let r21 = 2.0 * (y * z + x * w);
+    let r22 = 1.0 - 2.0 * (x * x + y * y);
+
+    let ry = r02.clamp(-1.0, 1.0).asin();
+    if r02.abs() < 0.99999 {
+        Vec3::new((-r12).atan2(r22), ry, (-r01).atan2(r00))
+    } else {
+        // gimbal lock, the z rotation is folded into x
+        Vec3::new(r21.atan2(r11), ry, 0.0)
+    }
+}
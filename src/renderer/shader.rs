@@ -1,38 +1,190 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::collections::HashMap;
 use std::ffi::CString;
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::ptr::{null, null_mut};
+use std::time::SystemTime;
+use crate::renderer::error::RendererError;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::preprocessor::{ExpandedSource, Preprocessor};
+use crate::renderer::program_cache::ProgramCache;
+use crate::renderer::reflection::{GlslType, ProgramReflection};
+use crate::renderer::shader_variants::Defines;
+use crate::renderer::shader_watcher;
+use crate::renderer::spirv::{self, SpecConstants, SpecValue};
+use crate::renderer::texture::TextureUnit;
 
 pub trait UniformValue {
     unsafe fn set_uniform(&self, location: i32);
+
+    /// Whether a uniform of type `ty` can be set from this type.
+    fn accepts(ty: GlslType) -> bool;
+
+    /// Number of array elements set, 1 for single values.
+    fn element_count(&self) -> usize {
+        1
+    }
 }
 
 pub struct Shader {
     id: u32,
     
     uniforms: HashMap<String, i32>,
-    vertex_path: PathBuf,
-    fragment_path: PathBuf,
+    reflection: ProgramReflection,
+    stages: Vec<(ShaderType, StageSource)>,
+    preprocessor: Preprocessor,
+    defines: Defines,
+    constants: SpecConstants,
+    cache: Option<ProgramCache>,
+    /// Stage files and everything they include, with their modification time at compile time.
+    dependencies: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Configures a `Shader` before compiling it: its stages and defines that are
+/// injected after `#version` in every stage.
+pub struct ShaderBuilder {
+    stages: Vec<(ShaderType, StageSource)>,
+    preprocessor: Preprocessor,
+    defines: Defines,
+    constants: SpecConstants,
+    cache: Option<ProgramCache>,
+}
+
+/// Where the code of a stage comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StageSource {
+    /// GLSL source, run through the preprocessor and the defines.
+    Glsl(PathBuf),
+    /// Precompiled SPIR-V module, specialized with the program's `SpecConstants`.
+    /// Needs `spirv::is_supported`.
+    Spirv {
+        path: PathBuf,
+        entry_point: String,
+    },
+}
+
+impl StageSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            StageSource::Glsl(path) => path,
+            StageSource::Spirv { path, .. } => path,
+        }
+    }
+
+    pub fn is_spirv(&self) -> bool {
+        matches!(self, StageSource::Spirv { .. })
+    }
+}
+
+/// A stage's code, read and ready to compile.
+enum LoadedSource {
+    Glsl(ExpandedSource),
+    Spirv(Vec<u8>),
+}
+
+impl LoadedSource {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            LoadedSource::Glsl(expanded) => expanded.source.as_bytes(),
+            LoadedSource::Spirv(module) => module,
+        }
+    }
 }
 
-enum ShaderType {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderType {
     Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
     Fragment,
+    Compute,
+}
+
+/// Why a program couldn't be built or reloaded.
+#[derive(Clone, Debug)]
+pub enum ShaderError {
+    /// A stage file or one of its includes couldn't be read or expanded.
+    Preprocess(String),
+    /// A stage failed to compile. File and line numbers in `log` point to the
+    /// original files, not the expanded source.
+    Compile {
+        stage: ShaderType,
+        path: PathBuf,
+        log: String,
+    },
+    Link {
+        log: String,
+    },
+    /// The combination of stages can't form a program.
+    Stages(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Preprocess(message) => write!(f, "{}", message),
+            ShaderError::Compile { stage, path, log } => write!(f, "Could not compile {:?} shader {}:\n{}", stage, path.display(), log),
+            ShaderError::Link { log } => write!(f, "Could not link program: {}", log),
+            ShaderError::Stages(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl ShaderType {
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            ShaderType::Vertex => gl::VERTEX_SHADER,
+            ShaderType::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderType::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderType::Geometry => gl::GEOMETRY_SHADER,
+            ShaderType::Fragment => gl::FRAGMENT_SHADER,
+            ShaderType::Compute => gl::COMPUTE_SHADER,
+        }
+    }
 }
 
 impl Shader {
-    pub fn new(vertex_path: &PathBuf, fragment_path: &PathBuf) -> Result<Self, String> {
-        let vertex = Self::create_shader(ShaderType::Vertex, vertex_path)?;
-        let fragment = Self::create_shader(ShaderType::Fragment, fragment_path)?;
+    /// Compiles the stages with the default preprocessor, which looks for
+    /// includes next to the including file and in `res/shaders`.
+    pub fn new(vertex_path: &Path, fragment_path: &Path) -> Result<Self, ShaderError> {
+        Self::with_preprocessor(vertex_path, fragment_path, Preprocessor::default())
+    }
 
-        let program = Self::create_program(vertex, fragment)?;
+    pub fn with_preprocessor(vertex_path: &Path, fragment_path: &Path, preprocessor: Preprocessor) -> Result<Self, ShaderError> {
+        Self::builder(vertex_path, fragment_path).preprocessor(preprocessor).build()
+    }
 
-        Ok(Self {
-            id: program,
-            uniforms: HashMap::new(),
-            vertex_path: vertex_path.clone(),
-            fragment_path: fragment_path.clone()
-        })
+    /// Builder for a vertex/fragment program, more stages can be added to it.
+    pub fn builder(vertex_path: &Path, fragment_path: &Path) -> ShaderBuilder {
+        ShaderBuilder::new()
+            .stage(ShaderType::Vertex, vertex_path)
+            .stage(ShaderType::Fragment, fragment_path)
+    }
+
+    /// Builder for a vertex/fragment program from SPIR-V modules with `main` as
+    /// the entry point. Set specialization constants with `specialize`.
+    pub fn spirv_builder(vertex_path: &Path, fragment_path: &Path) -> ShaderBuilder {
+        ShaderBuilder::new()
+            .spirv_stage(ShaderType::Vertex, vertex_path, "main")
+            .spirv_stage(ShaderType::Fragment, fragment_path, "main")
+    }
+
+    /// A standalone compute program, run with `dispatch`.
+    pub fn compute(path: &Path) -> Result<Self, ShaderError> {
+        ShaderBuilder::new().stage(ShaderType::Compute, path).build()
+    }
+
+    /// Wraps the shader for sharing between models and registers it with
+    /// `shader_watcher`, so it is recompiled when its files change.
+    pub fn shared(self) -> Rc<RefCell<Self>> {
+        let shader = Rc::new(RefCell::new(self));
+        shader_watcher::watch(&shader);
+        shader
     }
 
     pub fn id(&self) -> u32 {
@@ -51,13 +203,51 @@ impl Shader {
         }
     }
 
+    pub fn is_compute(&self) -> bool {
+        self.stages.iter().any(|(stage, _)| *stage == ShaderType::Compute)
+    }
+
+    /// Runs the compute program with `x * y * z` work groups. Results written to
+    /// buffers or images are only visible to later commands after a matching
+    /// `compute::memory_barrier`.
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        debug_assert!(self.is_compute(), "dispatch on a program without a compute stage");
+        self.bind();
+        unsafe {
+            gl::DispatchCompute(x, y, z);
+        }
+    }
+
+    /// Work groups needed to cover `size` invocations with the program's local size.
+    pub fn work_groups_for(&self, size: [u32; 3]) -> [u32; 3] {
+        let local = self.work_group_size();
+        [0, 1, 2].map(|i| size[i].div_ceil(local[i].max(1)))
+    }
+
+    /// The `local_size_x/y/z` the compute stage declares.
+    pub fn work_group_size(&self) -> [u32; 3] {
+        let mut size = [1i32; 3];
+        if self.is_compute() {
+            unsafe {
+                gl::GetProgramiv(self.id, gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+            }
+        }
+        size.map(|s| s as u32)
+    }
+
+    /// Location of the uniform `name`, `None` if the program has no such uniform
+    /// or the name contains a nul byte.
     pub fn get_uniform_location(&mut self, name: &str) -> Option<i32> {
+        if let Some(uniform) = self.reflection.uniform(name) {
+            return Some(uniform.location);
+        }
+        // array elements and the like aren't reflected by name
         if let Some(&location) = self.uniforms.get(name) {
             return Some(location);
         }
         
+        let c_name = CString::new(name).ok()?;
         let location = unsafe {
-            let c_name = CString::new(name).unwrap();
             gl::GetUniformLocation(self.id, c_name.as_ptr())
         };
         
@@ -70,73 +260,387 @@ impl Shader {
         Some(location)
     }
 
-    pub fn set_uniform<T: UniformValue>(&self, location: i32, value: T) {
+    /// Sets a uniform of the bound program. Returns an error without setting
+    /// anything if the uniform has a different type or an array gets more
+    /// elements than it has left from `location` on.
+    pub fn set_uniform<T: UniformValue>(&self, location: i32, value: T) -> Result<(), RendererError> {
+        if let Some(uniform) = self.reflection.uniform_at(location) {
+            if !T::accepts(uniform.ty) {
+                return Err(RendererError::Invalid(format!("Uniform '{}' is a {} and can't be set from {}", uniform.name, uniform.ty, std::any::type_name::<T>())));
+            }
+            let remaining = (uniform.location + uniform.array_size - location) as usize;
+            if value.element_count() > remaining {
+                return Err(RendererError::Invalid(format!("{} values don't fit into the {} elements of uniform '{}' from location {} on", value.element_count(), remaining, uniform.name, location)));
+            }
+        }
         unsafe {
             value.set_uniform(location);
         }
+        Ok(())
+    }
+
+    /// Looks up `name` and sets it on the bound program. Returns false if the
+    /// program has no such uniform and an error if it has a different type.
+    pub fn set<T: UniformValue>(&mut self, name: &str, value: T) -> Result<bool, RendererError> {
+        match self.get_uniform_location(name) {
+            Some(location) => self.set_uniform(location, value).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Active uniforms, blocks and vertex inputs of the program.
+    pub fn reflection(&self) -> &ProgramReflection {
+        &self.reflection
+    }
+
+    /// Checks that the vertex layout of `mesh` supplies every input of the vertex stage.
+    pub fn check_mesh(&self, mesh: &Mesh) -> Result<(), RendererError> {
+        self.reflection.check_vertex_layout(mesh.layout())
     }
     
     
-    pub fn reload(&mut self) -> Result<(), String> {
-        let vertex = Self::create_shader(ShaderType::Vertex, &self.vertex_path)?;
-        let fragment = Self::create_shader(ShaderType::Fragment, &self.fragment_path)?;
-        self.id = Self::create_program(vertex, fragment)?;
+    /// Recompiles from the files and replaces the program. On failure nothing
+    /// changes and the last good program stays in use.
+    pub fn reload(&mut self) -> Result<(), ShaderError> {
+        // a failed attempt shouldn't be retried until the files change again
+        for (path, compiled) in self.dependencies.iter_mut() {
+            *compiled = modified(path);
+        }
+        
+        let mut dependencies = Vec::new();
+        let program = match Self::compile(&self.stages, &self.preprocessor, &self.defines, &self.constants, self.cache.as_ref(), &mut dependencies) {
+            Ok(program) => program,
+            Err(e) => {
+                // files the broken version started including have to be watched too
+                for (path, compiled) in dependencies {
+                    if !self.dependencies.iter().any(|(known, _)| *known == path) {
+                        self.dependencies.push((path, compiled));
+                    }
+                }
+                return Err(e);
+            }
+        };
+        
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+        self.id = program;
+        // locations may differ in the new program
+        self.uniforms.clear();
+        self.reflection = ProgramReflection::reflect(self.id);
+        self.dependencies = dependencies;
         
         Ok(())
     }
+    
+    /// Stage files and all files they include.
+    pub fn dependencies(&self) -> impl Iterator<Item = &Path> {
+        self.dependencies.iter().map(|(path, _)| path.as_path())
+    }
+    
+    /// True when a stage file or one of its includes changed since the last compile.
+    pub fn is_stale(&self) -> bool {
+        self.dependencies.iter().any(|(path, compiled)| modified(path) != *compiled)
+    }
+    
+    /// Defines the stages were compiled with.
+    pub fn defines(&self) -> &Defines {
+        &self.defines
+    }
+    
+    pub fn stages(&self) -> impl Iterator<Item = (ShaderType, &StageSource)> {
+        self.stages.iter().map(|(stage, source)| (*stage, source))
+    }
+    
+    /// Specialization constants the SPIR-V stages were specialized with.
+    pub fn spec_constants(&self) -> &SpecConstants {
+        &self.constants
+    }
+    
+    /// Stage file names and defines, e.g. `default.vert+default.frag [ALPHA_TEST]`, for messages.
+    pub fn label(&self) -> String {
+        let files: Vec<String> = self.stages.iter()
+            .map(|(_, source)| source.path())
+            .map(|path| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned())
+            .collect();
+        let mut label = files.join("+");
+        if !self.defines.is_empty() {
+            let defines: Vec<&str> = self.defines.iter().map(|(name, _)| name).collect();
+            label.push_str(&format!(" [{}]", defines.join(" ")));
+        }
+        label
+    }
+
+    /// Compiles and links the stages, or loads the program from `cache` if the
+    /// same sources were linked before. Shader objects are deleted in any case.
+    fn compile(stages: &[(ShaderType, StageSource)], preprocessor: &Preprocessor, defines: &Defines, constants: &SpecConstants, cache: Option<&ProgramCache>, dependencies: &mut Vec<(PathBuf, Option<SystemTime>)>) -> Result<u32, ShaderError> {
+        let sources = stages.iter()
+            .map(|(stage, source)| Ok((*stage, source, Self::load(source, preprocessor, defines, dependencies)?)))
+            .collect::<Result<Vec<_>, ShaderError>>()?;
+
+        let cached = cache.map(|cache| {
+            let constants = constants.to_bytes();
+            let stages = sources.iter().map(|(stage, _, loaded)| (stage.gl_type(), loaded.bytes()));
+            // one module can hold several entry points
+            let entry_points = sources.iter().filter_map(|(stage, source, _)| match source {
+                StageSource::Spirv { entry_point, .. } => Some((stage.gl_type(), entry_point.as_bytes())),
+                StageSource::Glsl(_) => None,
+            });
+            (cache, ProgramCache::key(stages.chain(entry_points).chain([(0, constants.as_slice())])))
+        });
+        if let Some(program) = cached.and_then(|(cache, key)| cache.load(key)) {
+            return Ok(program);
+        }
+
+        let mut shaders = Vec::with_capacity(sources.len());
+        let result = sources.iter()
+            .try_for_each(|(stage, source, loaded)| {
+                let shader = match (source, loaded) {
+                    (StageSource::Spirv { entry_point, .. }, LoadedSource::Spirv(module)) => {
+                        spirv::create_shader(stage.gl_type(), module, entry_point, constants)
+                            .map_err(|log| ShaderError::Compile { stage: *stage, path: source.path().to_path_buf(), log })?
+                    }
+                    (_, LoadedSource::Glsl(expanded)) => Self::create_shader(*stage, source.path(), expanded)?,
+                    (_, LoadedSource::Spirv(_)) => unreachable!("SPIR-V is only loaded for SPIR-V stages"),
+                };
+                shaders.push(shader);
+                Ok(())
+            })
+            .and_then(|_| Self::create_program(&shaders, cache.is_some()));
+        for shader in shaders {
+            unsafe {
+                gl::DeleteShader(shader);
+            }
+        }
+        if let (Ok(program), Some((cache, key))) = (&result, cached) {
+            cache.store(key, *program);
+        }
+        result
+    }
 
-    fn create_shader(shader_type: ShaderType, path: &PathBuf) -> Result<u32, String> {
-        let shader_src = std::fs::read_to_string(path).unwrap();
-        let shader_src = CString::new(shader_src).unwrap();
+    /// Reads a stage and records every file read in `dependencies`.
+    fn load(source: &StageSource, preprocessor: &Preprocessor, defines: &Defines, dependencies: &mut Vec<(PathBuf, Option<SystemTime>)>) -> Result<LoadedSource, ShaderError> {
+        match source {
+            StageSource::Glsl(path) => Ok(LoadedSource::Glsl(Self::expand(path, preprocessor, defines, dependencies)?)),
+            StageSource::Spirv { path, .. } => {
+                let module = std::fs::read(path)
+                    .map_err(|e| ShaderError::Preprocess(format!("Could not read {}: {}", path.display(), e)))?;
+                if !dependencies.iter().any(|(known, _)| known == path) {
+                    dependencies.push((path.clone(), modified(path)));
+                }
+                Ok(LoadedSource::Spirv(module))
+            }
+        }
+    }
+
+    /// Resolves includes, injects the defines and records every file read in `dependencies`.
+    fn expand(path: &Path, preprocessor: &Preprocessor, defines: &Defines, dependencies: &mut Vec<(PathBuf, Option<SystemTime>)>) -> Result<ExpandedSource, ShaderError> {
+        let mut expanded = preprocessor.process(path).map_err(ShaderError::Preprocess)?;
+        if !defines.is_empty() {
+            expanded.insert_after_version(&defines.to_glsl());
+        }
+        for file in expanded.files.iter() {
+            if !dependencies.iter().any(|(known, _)| known == file) {
+                dependencies.push((file.clone(), modified(file)));
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn create_shader(shader_type: ShaderType, path: &Path, expanded: &ExpandedSource) -> Result<u32, ShaderError> {
+        let shader_src = CString::new(expanded.source.as_str())
+            .map_err(|_| ShaderError::Preprocess(format!("{} contains a nul byte", path.display())))?;
         unsafe {
-            let shader_type = match shader_type {
-                ShaderType::Vertex => gl::VERTEX_SHADER,
-                ShaderType::Fragment => gl::FRAGMENT_SHADER,
-            };
-            let shader = gl::CreateShader(shader_type);
+            let shader = gl::CreateShader(shader_type.gl_type());
             gl::ShaderSource(shader, 1, &shader_src.as_ptr(), null());
             gl::CompileShader(shader);
 
             let mut success = 0;
             gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
             if success != 1 {
-                const LOG_SIZE: usize = 512;
-                let mut log = [0i8; LOG_SIZE];
-                gl::GetShaderInfoLog(shader, LOG_SIZE as i32, null_mut(), log.as_mut_ptr());
-                let log_str = std::ffi::CStr::from_ptr(log.as_ptr()).to_string_lossy();
-                return Err(std::format!("Could not compile shader: {}", log_str));
+                let mut log_len = 0;
+                gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_len);
+                let mut log = vec![0u8; log_len.max(1) as usize];
+                gl::GetShaderInfoLog(shader, log.len() as i32, null_mut(), log.as_mut_ptr() as *mut _);
+                gl::DeleteShader(shader);
+                let log_str = String::from_utf8_lossy(&log);
+                let log_str = log_str.trim_end_matches('\0');
+                return Err(ShaderError::Compile { stage: shader_type, path: path.to_path_buf(), log: expanded.rewrite_log(log_str) });
             }
 
             Ok(shader)
         }
     }
 
-    fn create_program(vertex: u32, fragment: u32) -> Result<u32, String> {
+    /// Links the shaders into a new program. The shaders are detached again,
+    /// deleting them is up to the caller.
+    fn create_program(shaders: &[u32], retrievable: bool) -> Result<u32, ShaderError> {
         unsafe {
             let program = gl::CreateProgram();
+            if retrievable {
+                gl::ProgramParameteri(program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+            }
 
-            gl::AttachShader(program, vertex);
-            gl::AttachShader(program, fragment);
+            for &shader in shaders {
+                gl::AttachShader(program, shader);
+            }
             gl::LinkProgram(program);
+            for &shader in shaders {
+                gl::DetachShader(program, shader);
+            }
 
             let mut success = 0;
             gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
             if success != 1 {
-                const LOG_SIZE: usize = 512;
-                let mut log = [0i8; LOG_SIZE];
-                gl::GetProgramInfoLog(program, LOG_SIZE as i32, null_mut(), log.as_mut_ptr());
-                let log_str = std::ffi::CStr::from_ptr(log.as_ptr()).to_string_lossy();
-                return Err(std::format!("Could not link program: {}", log_str));
+                let mut log_len = 0;
+                gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut log_len);
+                let mut log = vec![0u8; log_len.max(1) as usize];
+                gl::GetProgramInfoLog(program, log.len() as i32, null_mut(), log.as_mut_ptr() as *mut _);
+                gl::DeleteProgram(program);
+                let log_str = String::from_utf8_lossy(&log);
+                return Err(ShaderError::Link { log: log_str.trim_end_matches('\0').to_string() });
             }
 
-            gl::DeleteShader(vertex);
-            gl::DeleteShader(fragment);
-
             Ok(program)
         }
     }
 }
 
+impl Default for ShaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderBuilder {
+    /// Builder without stages and with the default preprocessor.
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            preprocessor: Preprocessor::default(),
+            defines: Defines::new(),
+            constants: SpecConstants::new(),
+            cache: Some(ProgramCache::default()),
+        }
+    }
+
+    /// Adds a GLSL stage, replacing an earlier one of the same type.
+    pub fn stage(self, stage: ShaderType, path: &Path) -> Self {
+        self.stage_source(stage, StageSource::Glsl(path.to_path_buf()))
+    }
+
+    /// Adds a stage from a SPIR-V module, replacing an earlier one of the same type.
+    pub fn spirv_stage(self, stage: ShaderType, path: &Path, entry_point: &str) -> Self {
+        self.stage_source(stage, StageSource::Spirv { path: path.to_path_buf(), entry_point: entry_point.to_string() })
+    }
+
+    pub fn stage_source(mut self, stage: ShaderType, source: StageSource) -> Self {
+        self.stages.retain(|(existing, _)| *existing != stage);
+        self.stages.push((stage, source));
+        self
+    }
+
+    /// Sets the specialization constant `layout (constant_id = id)` of the SPIR-V stages.
+    pub fn specialize<T: SpecValue>(mut self, id: u32, value: T) -> Self {
+        self.constants.set(id, value);
+        self
+    }
+
+    pub fn geometry(self, path: &Path) -> Self {
+        self.stage(ShaderType::Geometry, path)
+    }
+
+    /// Adds both tessellation stages. Meshes drawn with it need `Topology::Patches`.
+    pub fn tessellation(self, control_path: &Path, evaluation_path: &Path) -> Self {
+        self.stage(ShaderType::TessControl, control_path)
+            .stage(ShaderType::TessEvaluation, evaluation_path)
+    }
+
+    pub fn preprocessor(mut self, preprocessor: Preprocessor) -> Self {
+        self.preprocessor = preprocessor;
+        self
+    }
+
+    /// Adds `#define name`.
+    pub fn define(mut self, name: &str) -> Self {
+        self.defines.set(name, "");
+        self
+    }
+
+    /// Adds `#define name value`.
+    pub fn define_value<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.defines.set(name, value);
+        self
+    }
+
+    /// Where linked programs are cached, `ProgramCache::default()` unless changed.
+    pub fn program_cache(mut self, cache: ProgramCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Always compiles from source.
+    pub fn no_program_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    /// Replaces all defines added so far.
+    pub fn defines(mut self, defines: Defines) -> Self {
+        self.defines = defines;
+        self
+    }
+
+    pub fn build(mut self) -> Result<Shader, ShaderError> {
+        self.validate_stages()?;
+        // compile in pipeline order so errors show up in a predictable order
+        self.stages.sort_by_key(|(stage, _)| *stage as u8);
+
+        let mut dependencies = Vec::new();
+        let program = Shader::compile(&self.stages, &self.preprocessor, &self.defines, &self.constants, self.cache.as_ref(), &mut dependencies)?;
+
+        Ok(Shader {
+            id: program,
+            uniforms: HashMap::new(),
+            reflection: ProgramReflection::reflect(program),
+            stages: self.stages,
+            preprocessor: self.preprocessor,
+            defines: self.defines,
+            constants: self.constants,
+            cache: self.cache,
+            dependencies,
+        })
+    }
+
+    fn validate_stages(&self) -> Result<(), ShaderError> {
+        let has = |stage| self.stages.iter().any(|(existing, _)| *existing == stage);
+        let spirv = self.stages.iter().filter(|(_, source)| source.is_spirv()).count();
+        if spirv > 0 && spirv < self.stages.len() {
+            return Err(ShaderError::Stages(String::from("SPIR-V and GLSL stages can't be linked into one program")));
+        }
+        if spirv > 0 && !spirv::is_supported() {
+            return Err(ShaderError::Stages(String::from("SPIR-V shaders need GL 4.6 or GL_ARB_gl_spirv")));
+        }
+        if has(ShaderType::Compute) {
+            if self.stages.len() > 1 {
+                return Err(ShaderError::Stages(String::from("A compute shader can't be linked with other stages")));
+            }
+            return Ok(());
+        }
+        if !has(ShaderType::Vertex) {
+            return Err(ShaderError::Stages(String::from("Program has no vertex shader")));
+        }
+        if has(ShaderType::TessControl) && !has(ShaderType::TessEvaluation) {
+            return Err(ShaderError::Stages(String::from("Tessellation control shader without an evaluation shader")));
+        }
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 impl Drop for Shader {
     fn drop(&mut self) {
         unsafe {
@@ -146,50 +650,312 @@ impl Drop for Shader {
 }
 
 // ----------- uniforms -----------
-#[allow(unsafe_op_in_unsafe_fn)]
-// Mat4
-impl UniformValue for glm::Mat4 {
+/// Types that can be set as a single uniform and as arrays, through `&[T]`, `[T; N]` and `Vec<T>`.
+pub trait UniformElement: Sized {
+    /// Sets `values.len()` consecutive array elements starting at `location`.
+    unsafe fn set_uniform_array(values: &[Self], location: i32);
+
+    /// Whether a uniform of type `ty` (the element type for arrays) can be set from this type.
+    fn accepts(ty: GlslType) -> bool;
+}
+
+impl<T: UniformElement> UniformValue for T {
     unsafe fn set_uniform(&self, location: i32) {
-        gl::UniformMatrix4fv(location, 1, gl::FALSE, self.as_array().as_ptr() as *const _);
+        unsafe { T::set_uniform_array(std::slice::from_ref(self), location) }
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        <T as UniformElement>::accepts(ty)
     }
 }
 
-#[allow(unsafe_op_in_unsafe_fn)]
-// Mat3
-impl UniformValue for glm::Mat3 {
+impl<T: UniformElement> UniformValue for &[T] {
     unsafe fn set_uniform(&self, location: i32) {
-        gl::UniformMatrix3fv(location, 1, gl::FALSE, self.as_array().as_ptr() as *const _);
+        unsafe { T::set_uniform_array(self, location) }
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        <T as UniformElement>::accepts(ty)
+    }
+
+    fn element_count(&self) -> usize {
+        self.len()
     }
 }
 
-#[allow(unsafe_op_in_unsafe_fn)]
-// Vec3
-impl UniformValue for glm::Vec3 {
+impl<T: UniformElement, const N: usize> UniformValue for [T; N] {
     unsafe fn set_uniform(&self, location: i32) {
-        gl::Uniform3fv(location, 1, self.as_array().as_ptr() as *const _);
+        unsafe { T::set_uniform_array(self, location) }
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        <T as UniformElement>::accepts(ty)
+    }
+
+    fn element_count(&self) -> usize {
+        self.len()
     }
 }
 
-#[allow(unsafe_op_in_unsafe_fn)]
-// Vec4
-impl UniformValue for glm::Vec4 {
+impl<T: UniformElement> UniformValue for Vec<T> {
     unsafe fn set_uniform(&self, location: i32) {
-        gl::Uniform4fv(location, 1, self.as_array().as_ptr() as *const _);
+        unsafe { T::set_uniform_array(self, location) }
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        <T as UniformElement>::accepts(ty)
+    }
+
+    fn element_count(&self) -> usize {
+        self.len()
     }
 }
 
 #[allow(unsafe_op_in_unsafe_fn)]
 // f32
-impl UniformValue for f32 {
-    unsafe fn set_uniform(&self, location: i32) {
-        gl::Uniform1f(location, *self);
+impl UniformElement for f32 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform1fv(location, values.len() as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Float
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// Vec2
+impl UniformElement for glm::Vec2 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform2fv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Vec2
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// Vec3
+impl UniformElement for glm::Vec3 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform3fv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Vec3
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// Vec4
+impl UniformElement for glm::Vec4 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform4fv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Vec4
     }
 }
 
 #[allow(unsafe_op_in_unsafe_fn)]
 // i32
-impl UniformValue for i32 {
-    unsafe fn set_uniform(&self, location: i32) {
-        gl::Uniform1i(location, *self);
+impl UniformElement for i32 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform1iv(location, values.len() as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        // samplers and images are set to their unit
+        matches!(ty, GlslType::Int | GlslType::Bool) || ty.is_opaque()
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// IVec2
+impl UniformElement for glm::IVec2 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform2iv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::IVec2 | GlslType::BVec2)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// IVec3
+impl UniformElement for glm::IVec3 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform3iv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::IVec3 | GlslType::BVec3)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// IVec4
+impl UniformElement for glm::IVec4 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform4iv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::IVec4 | GlslType::BVec4)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// u32
+impl UniformElement for u32 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform1uiv(location, values.len() as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::UInt | GlslType::Bool)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// UVec2
+impl UniformElement for glm::UVec2 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform2uiv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::UVec2 | GlslType::BVec2)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// UVec3
+impl UniformElement for glm::UVec3 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform3uiv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::UVec3 | GlslType::BVec3)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// UVec4
+impl UniformElement for glm::UVec4 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::Uniform4uiv(location, values.len() as i32, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        matches!(ty, GlslType::UVec4 | GlslType::BVec4)
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// bool, GL takes booleans as ints
+impl UniformElement for bool {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        let values: Vec<i32> = values.iter().map(|&b| b as i32).collect();
+        gl::Uniform1iv(location, values.len() as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Bool
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// BVec2
+impl UniformElement for glm::BVec2 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        let values: Vec<i32> = values.iter().flat_map(|b| [b.x as i32, b.y as i32]).collect();
+        gl::Uniform2iv(location, (values.len() / 2) as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::BVec2
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// BVec3
+impl UniformElement for glm::BVec3 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        let values: Vec<i32> = values.iter().flat_map(|b| [b.x as i32, b.y as i32, b.z as i32]).collect();
+        gl::Uniform3iv(location, (values.len() / 3) as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::BVec3
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// BVec4
+impl UniformElement for glm::BVec4 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        let values: Vec<i32> = values.iter().flat_map(|b| [b.x as i32, b.y as i32, b.z as i32, b.w as i32]).collect();
+        gl::Uniform4iv(location, (values.len() / 4) as i32, values.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::BVec4
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// Mat2
+impl UniformElement for glm::Mat2 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::UniformMatrix2fv(location, values.len() as i32, gl::FALSE, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Mat2
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// Mat3
+impl UniformElement for glm::Mat3 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::UniformMatrix3fv(location, values.len() as i32, gl::FALSE, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Mat3
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// Mat4
+impl UniformElement for glm::Mat4 {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        gl::UniformMatrix4fv(location, values.len() as i32, gl::FALSE, values.as_ptr() as *const _);
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty == GlslType::Mat4
+    }
+}
+
+#[allow(unsafe_op_in_unsafe_fn)]
+// textures, array elements go to consecutive samplers
+impl UniformElement for TextureUnit<'_> {
+    unsafe fn set_uniform_array(values: &[Self], location: i32) {
+        let units: Vec<i32> = values.iter().map(|value| {
+            value.texture.bind_to_unit(value.unit);
+            value.unit as i32
+        }).collect();
+        gl::Uniform1iv(location, units.len() as i32, units.as_ptr());
+    }
+
+    fn accepts(ty: GlslType) -> bool {
+        ty.is_sampler()
     }
 }
@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::renderer::error::RendererError;
+use crate::renderer::material::Material;
+use crate::renderer::preprocessor::Preprocessor;
+use crate::renderer::shader::Shader;
+
+/// `#define`s a shader is compiled with. Kept sorted, so equal sets produce
+/// the same source and can be used as cache keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Defines {
+    values: BTreeMap<String, String>,
+}
+
+impl Defines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `#define name` without a value.
+    pub fn with(mut self, name: &str) -> Self {
+        self.set(name, "");
+        self
+    }
+
+    pub fn with_value<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn set<T: ToString>(&mut self, name: &str, value: T) {
+        self.values.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.values.remove(name);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// One `#define` line per entry.
+    pub fn to_glsl(&self) -> String {
+        self.values.iter().map(|(name, value)| {
+            if value.is_empty() {
+                format!("#define {}\n", name)
+            } else {
+                format!("#define {} {}\n", name, value)
+            }
+        }).collect()
+    }
+}
+
+/// Compiles one vertex/fragment pair with different sets of defines and
+/// caches every variant, so models asking for the same set share a program.
+pub struct ShaderVariants {
+    vertex_path: PathBuf,
+    fragment_path: PathBuf,
+    preprocessor: Preprocessor,
+    variants: HashMap<Defines, Rc<RefCell<Shader>>>,
+}
+
+impl ShaderVariants {
+    pub fn new(vertex_path: &Path, fragment_path: &Path) -> Self {
+        Self::with_preprocessor(vertex_path, fragment_path, Preprocessor::default())
+    }
+
+    pub fn with_preprocessor(vertex_path: &Path, fragment_path: &Path, preprocessor: Preprocessor) -> Self {
+        Self {
+            vertex_path: vertex_path.to_path_buf(),
+            fragment_path: fragment_path.to_path_buf(),
+            preprocessor,
+            variants: HashMap::new(),
+        }
+    }
+
+    /// The variant for `defines`, compiled on first use.
+    pub fn get(&mut self, defines: &Defines) -> Result<Rc<RefCell<Shader>>, RendererError> {
+        if let Some(shader) = self.variants.get(defines) {
+            return Ok(shader.clone());
+        }
+
+        let shader = Shader::builder(&self.vertex_path, &self.fragment_path)
+            .preprocessor(self.preprocessor.clone())
+            .defines(defines.clone())
+            .build()?
+            .shared();
+        self.variants.insert(defines.clone(), shader.clone());
+        Ok(shader)
+    }
+
+    /// The variant matching the features `material` uses, see `Material::defines`.
+    pub fn for_material(&mut self, material: &Material) -> Result<Rc<RefCell<Shader>>, RendererError> {
+        self.get(&material.defines())
+    }
+
+    /// Every variant compiled so far.
+    pub fn iter(&self) -> impl Iterator<Item = (&Defines, &Rc<RefCell<Shader>>)> {
+        self.variants.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+}
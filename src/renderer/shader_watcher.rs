@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+use crate::renderer::shader::{Shader, ShaderError};
+
+/// How often `poll` looks at the files. Checking modification times is cheap,
+/// but not cheap enough for every frame with many shaders.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Outcome of reloading one shader.
+pub struct ReloadEvent {
+    /// The shader's stage files, see `Shader::label`.
+    pub label: String,
+    /// On failure the shader keeps its last good program.
+    pub result: Result<(), ShaderError>,
+}
+
+struct Watched {
+    shaders: Vec<Weak<RefCell<Shader>>>,
+    last_poll: Option<Instant>,
+}
+
+thread_local! {
+    // GL objects belong to the thread of their context
+    static WATCHED: RefCell<Watched> = const { RefCell::new(Watched { shaders: Vec::new(), last_poll: None }) };
+}
+
+/// Recompiles `shader` whenever one of its stage files or includes changes.
+/// Only a weak reference is kept, dropped shaders stop being watched.
+/// `Shader::shared` does this for you.
+pub fn watch(shader: &Rc<RefCell<Shader>>) {
+    WATCHED.with_borrow_mut(|watched| {
+        if !watched.shaders.iter().any(|known| known.as_ptr() == Rc::as_ptr(shader)) {
+            watched.shaders.push(Rc::downgrade(shader));
+        }
+    });
+}
+
+/// Reloads the watched shaders whose files changed. Call once per frame, the
+/// files are only checked every `POLL_INTERVAL`. Models sharing a shader see
+/// the new program right away.
+pub fn poll() -> Vec<ReloadEvent> {
+    let due = WATCHED.with_borrow_mut(|watched| {
+        let now = Instant::now();
+        let due = watched.last_poll.is_none_or(|last| now - last >= POLL_INTERVAL);
+        if due {
+            watched.last_poll = Some(now);
+        }
+        due
+    });
+    if !due {
+        return Vec::new();
+    }
+    reload_where(|shader| shader.is_stale())
+}
+
+/// Reloads every watched shader, changed or not.
+pub fn reload_all() -> Vec<ReloadEvent> {
+    reload_where(|_| true)
+}
+
+fn reload_where(condition: impl Fn(&Shader) -> bool) -> Vec<ReloadEvent> {
+    // the list is copied so reloading can't conflict with `watch` calls
+    let shaders: Vec<Rc<RefCell<Shader>>> = WATCHED.with_borrow_mut(|watched| {
+        watched.shaders.retain(|shader| shader.strong_count() > 0);
+        watched.shaders.iter().filter_map(Weak::upgrade).collect()
+    });
+
+    shaders.iter()
+        .filter(|shader| condition(&shader.borrow()))
+        .map(|shader| {
+            let mut shader = shader.borrow_mut();
+            ReloadEvent { label: shader.label(), result: shader.reload() }
+        })
+        .collect()
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void, CString};
+use std::ptr::null_mut;
+use std::sync::OnceLock;
+use crate::renderer::gl_caps;
+
+/// `GL_SHADER_BINARY_FORMAT_SPIR_V`, GL 4.6 isn't covered by the `gl` bindings.
+const SHADER_BINARY_FORMAT_SPIR_V: u32 = 0x9551;
+const MAGIC: u32 = 0x07230203;
+
+type SpecializeShaderFn = unsafe extern "system" fn(u32, *const c_char, u32, *const u32, *const u32);
+
+static SPECIALIZE_SHADER: OnceLock<Option<SpecializeShaderFn>> = OnceLock::new();
+
+/// Loads `glSpecializeShader`, which the `gl` bindings don't have. Call it next
+/// to `gl::load_with` with the same loader.
+pub fn load_with<F: FnMut(&str) -> *const c_void>(mut loader: F) {
+    SPECIALIZE_SHADER.get_or_init(|| {
+        ["glSpecializeShader", "glSpecializeShaderARB"].into_iter()
+            .map(&mut loader)
+            .find(|ptr| !ptr.is_null())
+            .map(|ptr| unsafe { std::mem::transmute::<*const c_void, SpecializeShaderFn>(ptr) })
+    });
+}
+
+/// True if the context takes SPIR-V modules (GL 4.6 or `GL_ARB_gl_spirv`) and
+/// `load_with` found the entry point.
+pub fn is_supported() -> bool {
+    gl_caps::get().spirv && SPECIALIZE_SHADER.get().is_some_and(Option::is_some)
+}
+
+/// Values a specialization constant can take.
+pub trait SpecValue {
+    /// The 32 bits passed to `glSpecializeShader`.
+    fn to_bits(&self) -> u32;
+}
+
+impl SpecValue for u32 {
+    fn to_bits(&self) -> u32 {
+        *self
+    }
+}
+
+impl SpecValue for i32 {
+    fn to_bits(&self) -> u32 {
+        *self as u32
+    }
+}
+
+impl SpecValue for f32 {
+    fn to_bits(&self) -> u32 {
+        f32::to_bits(*self)
+    }
+}
+
+impl SpecValue for bool {
+    fn to_bits(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// Values for `layout (constant_id = N)` constants, applied to every SPIR-V
+/// stage of a program, ordered by id. Constants not set keep their default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SpecConstants {
+    constants: BTreeMap<u32, u32>,
+}
+
+impl SpecConstants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<T: SpecValue>(mut self, id: u32, value: T) -> Self {
+        self.set(id, value);
+        self
+    }
+
+    pub fn set<T: SpecValue>(&mut self, id: u32, value: T) {
+        self.constants.insert(id, value.to_bits());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.constants.iter().map(|(&id, &bits)| (id, bits))
+    }
+
+    /// Ids and values as bytes, for cache keys.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.constants.iter().flat_map(|(id, bits)| id.to_le_bytes().into_iter().chain(bits.to_le_bytes())).collect()
+    }
+}
+
+/// Checks the SPIR-V header, so garbage isn't handed to the driver.
+pub fn validate_module(module: &[u8]) -> Result<(), String> {
+    if module.len() < 20 || !module.len().is_multiple_of(4) {
+        return Err(format!("SPIR-V module has an invalid size of {} bytes", module.len()));
+    }
+    let magic = u32::from_le_bytes([module[0], module[1], module[2], module[3]]);
+    if magic != MAGIC {
+        return Err(format!("Not a SPIR-V module, magic number is 0x{:08x}", magic));
+    }
+    Ok(())
+}
+
+/// Creates a shader object of `shader_type` from a SPIR-V module and specializes
+/// `entry_point`. Returns the shader or the info log.
+pub fn create_shader(shader_type: u32, module: &[u8], entry_point: &str, constants: &SpecConstants) -> Result<u32, String> {
+    let Some(Some(specialize)) = SPECIALIZE_SHADER.get().copied() else {
+        return Err(String::from("SPIR-V shaders need GL 4.6 or GL_ARB_gl_spirv and spirv::load_with"));
+    };
+    validate_module(module)?;
+    let entry_point = CString::new(entry_point).map_err(|_| String::from("Entry point contains a nul byte"))?;
+    let (ids, values): (Vec<u32>, Vec<u32>) = constants.iter().unzip();
+
+    unsafe {
+        let shader = gl::CreateShader(shader_type);
+        gl::ShaderBinary(1, &shader, SHADER_BINARY_FORMAT_SPIR_V, module.as_ptr() as *const _, module.len() as i32);
+        specialize(shader, entry_point.as_ptr(), ids.len() as u32, ids.as_ptr(), values.as_ptr());
+
+        let mut success = 0;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        if success != 1 {
+            let mut log_len = 0;
+            gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_len);
+            let mut log = vec![0u8; log_len.max(1) as usize];
+            gl::GetShaderInfoLog(shader, log.len() as i32, null_mut(), log.as_mut_ptr() as *mut _);
+            gl::DeleteShader(shader);
+            let log_str = String::from_utf8_lossy(&log);
+            return Err(log_str.trim_end_matches('\0').to_string());
+        }
+        Ok(shader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_constants_are_ordered_by_id() {
+        let a = SpecConstants::new().with(3, 1.5f32).with(0, true).with(7, -2);
+        let b = SpecConstants::new().with(7, -2).with(0, false).with(3, 1.5f32).with(0, true);
+        assert_eq!(a, b);
+        assert_eq!(a.to_bytes(), b.to_bytes());
+        assert_eq!(a.iter().collect::<Vec<_>>(), [(0, 1), (3, 1.5f32.to_bits()), (7, -2i32 as u32)]);
+    }
+}
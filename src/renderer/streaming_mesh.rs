@@ -0,0 +1,105 @@
+use std::marker::PhantomData;
+use crate::renderer::buffer::{Buffer, MappedRing, VAO};
+use crate::renderer::error::RendererError;
+use crate::renderer::mesh::Topology;
+use crate::renderer::vertex::Vertex;
+use crate::renderer::vertex_layout::VertexLayout;
+
+/// Sections of the ring buffers: one being written, the others still in flight.
+const SECTIONS: usize = 3;
+
+/// Mesh whose geometry is rewritten every frame, like CPU particles or debug
+/// line batches. Vertices and indices go into persistently mapped ring buffers,
+/// so updating never reallocates or waits on the driver.
+pub struct StreamingMesh<V: VertexLayout = Vertex> {
+    vao: VAO,
+    vertices: MappedRing,
+    indices: Option<MappedRing>,
+
+    vertex_capacity: usize,
+    index_capacity: usize,
+    vertex_count: usize,
+    index_count: usize,
+    topology: Topology,
+    _vertex: PhantomData<V>,
+}
+
+impl<V: VertexLayout> StreamingMesh<V> {
+    /// Room for `vertex_capacity` vertices and `index_capacity` indices per
+    /// frame. Without index capacity the mesh is drawn with `DrawArrays`.
+    /// Fails without support for persistently mapped buffers.
+    pub fn new(vertex_capacity: usize, index_capacity: usize, topology: Topology) -> Result<Self, RendererError> {
+        let vao = VAO::new();
+        let vertices = MappedRing::new(vertex_capacity * size_of::<V>(), SECTIONS)?;
+        let indices = (index_capacity > 0)
+            .then(|| MappedRing::new(index_capacity * size_of::<u32>(), SECTIONS))
+            .transpose()?;
+
+        vao.set_vertex_buffer(0, vertices.id(), V::ATTRIBUTES, size_of::<V>(), 0);
+        if let Some(indices) = &indices {
+            vao.set_element_buffer(indices.id());
+        }
+
+        Ok(Self {
+            vao,
+            vertices,
+            indices,
+            vertex_capacity,
+            index_capacity,
+            vertex_count: 0,
+            index_count: 0,
+            topology,
+            _vertex: PhantomData,
+        })
+    }
+
+    /// Sets the geometry for the next `render`. Blocks only if the GPU is
+    /// still drawing from the section that gets reused. Geometry exceeding the
+    /// capacity is rejected and the previous geometry kept.
+    pub fn update(&mut self, vertices: &[V], indices: &[u32]) -> Result<(), RendererError> {
+        if vertices.len() > self.vertex_capacity {
+            return Err(RendererError::Invalid(format!("{} vertices exceed the capacity of {}", vertices.len(), self.vertex_capacity)));
+        }
+        if indices.len() > self.index_capacity {
+            return Err(RendererError::Invalid(format!("{} indices exceed the capacity of {}", indices.len(), self.index_capacity)));
+        }
+
+        self.vertices.next_section();
+        self.vertices.write(vertices)?;
+        self.vertex_count = vertices.len();
+
+        if let Some(ring) = &mut self.indices {
+            ring.next_section();
+            ring.write(indices)?;
+        }
+        self.index_count = indices.len();
+        Ok(())
+    }
+
+    pub fn render(&self) {
+        let base_vertex = self.vertices.offset() / size_of::<V>();
+        self.vao.bind();
+        self.topology.set_patch_vertices();
+        unsafe {
+            match &self.indices {
+                Some(indices) => {
+                    gl::DrawElementsBaseVertex(self.topology.gl_mode(), self.index_count as i32, gl::UNSIGNED_INT, indices.offset() as *const _, base_vertex as i32);
+                }
+                None => gl::DrawArrays(self.topology.gl_mode(), base_vertex as i32, self.vertex_count as i32),
+            }
+        }
+
+        self.vertices.fence();
+        if let Some(indices) = &self.indices {
+            indices.fence();
+        }
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+}
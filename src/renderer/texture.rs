@@ -1,63 +1,157 @@
-use gl::types::{GLint, GLuint};
+use gl::types::GLint;
 use std::ffi::c_void;
 use std::path::Path;
 use glm::Vec3;
+use crate::renderer::error::{check_gl, RendererError};
+use crate::renderer::gl_caps;
+use crate::renderer::sampler::{MipFilter, SamplerDesc, Wrap};
+use crate::renderer::texture_format::{ColorSpace, Pixels, TextureFormat};
 
 pub struct Texture {
     texture_id: u32,
     width: i32,
     height: i32,
+    levels: i32,
+    format: TextureFormat,
+    sampler: SamplerDesc,
 }
 
 impl Texture {
-    pub fn new<P>(path: P) -> Result<Self, String>
+    /// Loads an image file with the default sampler. 8-bit colour images are
+    /// treated as sRGB, see `load`.
+    pub fn new<P>(path: P) -> Result<Self, RendererError>
     where
         P: AsRef<Path>,
     {
-        let image = image::open(path).unwrap();
-        let image = image.flipv();
-        let width = image.width() as i32;
-        let height = image.height() as i32;
-        let data = image.to_rgba8();
+        Self::load(path, ColorSpace::Srgb, SamplerDesc::default())
+    }
+
+    /// Loads an image file as sRGB colours. Mip levels are generated if `sampler` uses them.
+    pub fn with_sampler<P>(path: P, sampler: SamplerDesc) -> Result<Self, RendererError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load(path, ColorSpace::Srgb, sampler)
+    }
+
+    /// Loads an image file in the format inferred from it, see `TextureFormat::infer`.
+    /// .hdr and .exr files become float textures.
+    pub fn load<P>(path: P, color_space: ColorSpace, sampler: SamplerDesc) -> Result<Self, RendererError>
+    where
+        P: AsRef<Path>,
+    {
+        let image = Self::open(path.as_ref())?;
+        Self::from_image_with_format(&image, TextureFormat::infer(&image, color_space), sampler)
+    }
 
-        let texture_id = Self::create_and_upload_texture(width, height, data.as_ptr() as *const _)?;
+    /// Loads an image file and converts it to `format`.
+    pub fn with_format<P>(path: P, format: TextureFormat, sampler: SamplerDesc) -> Result<Self, RendererError>
+    where
+        P: AsRef<Path>,
+    {
+        let image = Self::open(path.as_ref())?;
+        Self::from_image_with_format(&image, format, sampler)
+    }
+    
+    /// Uploads an already decoded image as is, without flipping it.
+    pub fn from_image(image: &image::DynamicImage) -> Result<Self, RendererError> {
+        Self::from_image_with_sampler(image, SamplerDesc::default())
+    }
 
-        Ok(Self {
-            width,
-            height,
-            texture_id,
-        })
+    pub fn from_image_with_sampler(image: &image::DynamicImage, sampler: SamplerDesc) -> Result<Self, RendererError> {
+        Self::from_image_with_format(image, TextureFormat::infer(image, ColorSpace::Srgb), sampler)
+    }
+
+    /// Uploads an image converted to `format`. Depth formats can't be uploaded from images.
+    pub fn from_image_with_format(image: &image::DynamicImage, format: TextureFormat, sampler: SamplerDesc) -> Result<Self, RendererError> {
+        let pixels = Pixels::convert(image, format)?;
+        Self::create(image.width() as i32, image.height() as i32, format, pixels.as_ptr(), sampler)
     }
     
-    /// not really working rn
-    pub fn from_color(color: Vec3) -> Result<Self, String> {
-        let width = 1;
-        let height = 1;
+    /// 1x1 texture of a single sRGB colour, e.g. as a placeholder for a missing map.
+    pub fn from_color(color: Vec3) -> Result<Self, RendererError> {
         if color.x < 0.0 || color.x > 1.0
             || color.y < 0.0 || color.y > 1.0
             || color.z < 0.0 || color.z > 1.0 {
-            return Err(String::from(format!("Color has to be in range 0..1: Color: {:?}", color)));
+            return Err(RendererError::Invalid(format!("Color has to be in range 0..1: Color: {:?}", color)));
+        }
+        let to_byte = |channel: f32| (channel * 255.0).round() as u8;
+        let data: [u8; 4] = [to_byte(color.x), to_byte(color.y), to_byte(color.z), 255];
+        Self::create(1, 1, TextureFormat::Srgb8Alpha8, data.as_ptr() as *const _, SamplerDesc::default())
+    }
+
+    /// Texture without contents, e.g. as a framebuffer attachment.
+    pub fn empty(width: i32, height: i32) -> Result<Self, RendererError> {
+        Self::empty_with_format(width, height, TextureFormat::Rgba8)
+    }
+
+    /// Texture without contents in `format`, e.g. a float or depth attachment.
+    pub fn empty_with_format(width: i32, height: i32, format: TextureFormat) -> Result<Self, RendererError> {
+        let sampler = SamplerDesc::default().with_mip_filter(MipFilter::None).with_wrap(Wrap::ClampToEdge);
+        Self::create(width, height, format, std::ptr::null(), sampler)
+    }
+    
+    pub fn id(&self) -> u32 {
+        self.texture_id
+    }
+    
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Number of mip levels, 1 if the texture has no mipmaps.
+    pub fn levels(&self) -> i32 {
+        self.levels
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn sampler(&self) -> &SamplerDesc {
+        &self.sampler
+    }
+
+    /// Changes how the texture is sampled. Mip levels can't be added later, so a
+    /// texture created without them keeps sampling the base level only.
+    pub fn set_sampler(&mut self, sampler: SamplerDesc) {
+        self.sampler = sampler;
+        self.apply_sampler();
+    }
+
+    /// Recomputes the mip levels from the base level, e.g. after rendering into it.
+    pub fn generate_mipmaps(&self) {
+        if self.levels <= 1 {
+            return;
+        }
+        unsafe {
+            if gl_caps::dsa() {
+                gl::GenerateTextureMipmap(self.texture_id);
+            } else {
+                let previous = gl_caps::bound(gl::TEXTURE_BINDING_2D);
+                gl::BindTexture(gl::TEXTURE_2D, self.texture_id);
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+                gl::BindTexture(gl::TEXTURE_2D, previous);
+            }
         }
-        let r = (color.x * 255.0) as u8;
-        let g = (color.y * 255.0) as u8;
-        let b = (color.z * 255.0) as u8;
-        let a = 255u8;
-        let data: [u8; 4] = [r, g, b, a];
-        
-        println!("data: {data:?}");
-        let texture_id = Self::create_and_upload_texture(width, height, data.as_ptr() as *const _)?;
-        
-        Ok(Self {
-            width,
-            height,
-            texture_id
-        })
     }
 
     pub fn bind(&self) {
+        self.bind_to_unit(0);
+    }
+
+    pub fn bind_to_unit(&self, unit: u32) {
         unsafe {
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, self.texture_id);
+            if gl_caps::dsa() {
+                gl::BindTextureUnit(unit, self.texture_id);
+            } else {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(gl::TEXTURE_2D, self.texture_id);
+            }
         }
     }
 
@@ -66,44 +160,130 @@ impl Texture {
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
     }
+
+    fn open(path: &Path) -> Result<image::DynamicImage, RendererError> {
+        let image = image::open(path).map_err(|e| RendererError::image(path, e))?;
+        // image rows start at the top, GL expects the bottom row first
+        Ok(image.flipv())
+    }
+
+    fn create(width: i32, height: i32, format: TextureFormat, data: *const c_void, sampler: SamplerDesc) -> Result<Self, RendererError> {
+        // depth textures can't have mipmaps generated
+        let levels = if sampler.uses_mipmaps() && !format.is_depth() { mip_levels(width, height) } else { 1 };
+        let mut alignment = 4;
+        unsafe {
+            // rows of single and dual channel images aren't 4-byte aligned
+            gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        }
+        let texture_id = if gl_caps::dsa() {
+            Self::create_and_upload_texture_dsa(width, height, levels, format, data)
+        } else {
+            Self::create_and_upload_texture(width, height, levels, format, data)
+        };
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+        }
+
+        // dropping deletes the texture if anything below fails
+        let texture = Self { texture_id, width, height, levels, format, sampler };
+        texture.apply_sampler();
+        if !data.is_null() {
+            texture.generate_mipmaps();
+        }
+        check_gl("Texture upload")?;
+        Ok(texture)
+    }
+
+    fn apply_sampler(&self) {
+        let mut sampler = self.sampler;
+        if self.levels == 1 {
+            // a mipmap filter without mip levels makes the texture incomplete
+            sampler.mip_filter = MipFilter::None;
+        }
+
+        let id = self.texture_id;
+        if gl_caps::dsa() {
+            sampler.apply(
+                |name, value| unsafe { gl::TextureParameteri(id, name, value) },
+                |name, value| unsafe { gl::TextureParameterf(id, name, value) },
+                |name, values| unsafe { gl::TextureParameterfv(id, name, values.as_ptr()) },
+            );
+        } else {
+            let previous = gl_caps::bound(gl::TEXTURE_BINDING_2D);
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, id);
+            }
+            sampler.apply(
+                |name, value| unsafe { gl::TexParameteri(gl::TEXTURE_2D, name, value) },
+                |name, value| unsafe { gl::TexParameterf(gl::TEXTURE_2D, name, value) },
+                |name, values| unsafe { gl::TexParameterfv(gl::TEXTURE_2D, name, values.as_ptr()) },
+            );
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, previous);
+            }
+        }
+    }
     
-    fn create_and_upload_texture(width: i32, height: i32, data: *const c_void) -> Result<u32, String> {
+    fn create_and_upload_texture(width: i32, height: i32, levels: i32, format: TextureFormat, data: *const c_void) -> u32 {
         let mut texture_id: u32 = 0;
+        let previous = gl_caps::bound(gl::TEXTURE_BINDING_2D);
         unsafe {
             gl::GenTextures(1, &mut texture_id);
             gl::BindTexture(gl::TEXTURE_2D, texture_id);
-            
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_S,
-                gl::CLAMP_TO_BORDER as GLint,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_T,
-                gl::CLAMP_TO_BORDER as GLint,
-            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, levels - 1);
             
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as GLint,
+                format.internal_format() as GLint,
                 width,
                 height,
                 0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
+                format.pixel_format(),
+                format.pixel_type(),
                 data,
             );
-            
-            let err = gl::GetError();
-            if err != gl::NO_ERROR {
-                return Err(format!("Error loading texture: {}", err));
+            if levels > 1 && data.is_null() {
+                // allocates the levels, they are filled by `generate_mipmaps` later
+                gl::GenerateMipmap(gl::TEXTURE_2D);
             }
+            gl::BindTexture(gl::TEXTURE_2D, previous);
         }
         
-        Ok(texture_id)
+        texture_id
+    }
+    
+    fn create_and_upload_texture_dsa(width: i32, height: i32, levels: i32, format: TextureFormat, data: *const c_void) -> u32 {
+        let mut texture_id: u32 = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture_id);
+            gl::TextureStorage2D(texture_id, levels, format.internal_format(), width, height);
+            if !data.is_null() {
+                gl::TextureSubImage2D(texture_id, 0, 0, 0, width, height, format.pixel_format(), format.pixel_type(), data);
+            }
+        }
+        
+        texture_id
+    }
+}
+
+/// Levels of a full mip chain down to 1x1.
+fn mip_levels(width: i32, height: i32) -> i32 {
+    32 - (width.max(height).max(1) as u32).leading_zeros() as i32
+}
+
+/// A texture for a sampler uniform: setting it binds the texture to `unit`
+/// and points the sampler at that unit.
+#[derive(Clone, Copy)]
+pub struct TextureUnit<'a> {
+    pub texture: &'a Texture,
+    pub unit: u32,
+}
+
+impl<'a> TextureUnit<'a> {
+    pub fn new(texture: &'a Texture, unit: u32) -> Self {
+        Self { texture, unit }
     }
 }
 
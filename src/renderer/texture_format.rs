@@ -0,0 +1,233 @@
+use std::ffi::c_void;
+use image::DynamicImage;
+use crate::renderer::error::RendererError;
+
+/// How the colour channels of an image are to be interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colours as painted, e.g. albedo and emissive maps. Decoded to linear when sampled.
+    Srgb,
+    /// Data sampled as is, e.g. normal, roughness and mask maps.
+    Linear,
+}
+
+/// Internal format of a texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgba8,
+    Srgb8Alpha8,
+    R16,
+    Rg16,
+    Rgba16,
+    R16F,
+    Rg16F,
+    Rgba16F,
+    R32F,
+    Rg32F,
+    Rgba32F,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+    Depth32FStencil8,
+}
+
+impl TextureFormat {
+    /// The format for sampling `image` in `color_space`. sRGB images, grey ones
+    /// included, become `Srgb8Alpha8` so they are decoded and sample as grey
+    /// rather than red. Linear images keep their channel count and 16-bit
+    /// precision, e.g. masks and height maps. Float images are HDR and stay float.
+    pub fn infer(image: &DynamicImage, color_space: ColorSpace) -> Self {
+        match (image, color_space) {
+            (DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_), _) => TextureFormat::Rgba32F,
+            (_, ColorSpace::Srgb) => TextureFormat::Srgb8Alpha8,
+            (DynamicImage::ImageLuma8(_), ColorSpace::Linear) => TextureFormat::R8,
+            (DynamicImage::ImageLumaA8(_), ColorSpace::Linear) => TextureFormat::Rg8,
+            (DynamicImage::ImageLuma16(_), ColorSpace::Linear) => TextureFormat::R16,
+            (DynamicImage::ImageLumaA16(_), ColorSpace::Linear) => TextureFormat::Rg16,
+            (DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_), ColorSpace::Linear) => TextureFormat::Rgba16,
+            (_, ColorSpace::Linear) => TextureFormat::Rgba8,
+        }
+    }
+
+    pub fn internal_format(&self) -> u32 {
+        match self {
+            TextureFormat::R8 => gl::R8,
+            TextureFormat::Rg8 => gl::RG8,
+            TextureFormat::Rgba8 => gl::RGBA8,
+            TextureFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            TextureFormat::R16 => gl::R16,
+            TextureFormat::Rg16 => gl::RG16,
+            TextureFormat::Rgba16 => gl::RGBA16,
+            TextureFormat::R16F => gl::R16F,
+            TextureFormat::Rg16F => gl::RG16F,
+            TextureFormat::Rgba16F => gl::RGBA16F,
+            TextureFormat::R32F => gl::R32F,
+            TextureFormat::Rg32F => gl::RG32F,
+            TextureFormat::Rgba32F => gl::RGBA32F,
+            TextureFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            TextureFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            TextureFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+            TextureFormat::Depth32FStencil8 => gl::DEPTH32F_STENCIL8,
+        }
+    }
+
+    /// Format of the pixel data passed to `glTexImage2D`.
+    pub fn pixel_format(&self) -> u32 {
+        match self {
+            TextureFormat::R8 | TextureFormat::R16 | TextureFormat::R16F | TextureFormat::R32F => gl::RED,
+            TextureFormat::Rg8 | TextureFormat::Rg16 | TextureFormat::Rg16F | TextureFormat::Rg32F => gl::RG,
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 | TextureFormat::Rgba16 | TextureFormat::Rgba16F | TextureFormat::Rgba32F => gl::RGBA,
+            TextureFormat::Depth24 | TextureFormat::Depth32F => gl::DEPTH_COMPONENT,
+            TextureFormat::Depth24Stencil8 | TextureFormat::Depth32FStencil8 => gl::DEPTH_STENCIL,
+        }
+    }
+
+    /// Type of the pixel data passed to `glTexImage2D`. Half float formats are
+    /// uploaded from 32-bit floats.
+    pub fn pixel_type(&self) -> u32 {
+        match self {
+            TextureFormat::R8 | TextureFormat::Rg8 | TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => gl::UNSIGNED_BYTE,
+            TextureFormat::R16 | TextureFormat::Rg16 | TextureFormat::Rgba16 => gl::UNSIGNED_SHORT,
+            TextureFormat::Depth24 => gl::UNSIGNED_INT,
+            TextureFormat::Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            TextureFormat::Depth32FStencil8 => gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+            _ => gl::FLOAT,
+        }
+    }
+
+    pub fn is_depth(&self) -> bool {
+        self.pixel_format() == gl::DEPTH_COMPONENT || self.has_stencil()
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        self.pixel_format() == gl::DEPTH_STENCIL
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        *self == TextureFormat::Srgb8Alpha8
+    }
+
+    /// Framebuffer attachment point of depth formats.
+    pub fn depth_attachment(&self) -> Option<u32> {
+        match self {
+            TextureFormat::Depth24 | TextureFormat::Depth32F => Some(gl::DEPTH_ATTACHMENT),
+            TextureFormat::Depth24Stencil8 | TextureFormat::Depth32FStencil8 => Some(gl::DEPTH_STENCIL_ATTACHMENT),
+            _ => None,
+        }
+    }
+}
+
+/// Pixels of an image converted for upload in some `TextureFormat`.
+pub(crate) enum Pixels {
+    Bytes(Vec<u8>),
+    Shorts(Vec<u16>),
+    Floats(Vec<f32>),
+}
+
+impl Pixels {
+    /// Converts `image` to the channels and type `format` is uploaded from.
+    pub(crate) fn convert(image: &DynamicImage, format: TextureFormat) -> Result<Self, RendererError> {
+        let pixels = match format {
+            TextureFormat::R8 => Pixels::Bytes(image.to_luma8().into_raw()),
+            TextureFormat::Rg8 => Pixels::Bytes(image.to_luma_alpha8().into_raw()),
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => Pixels::Bytes(image.to_rgba8().into_raw()),
+            TextureFormat::R16 => Pixels::Shorts(image.to_luma16().into_raw()),
+            TextureFormat::Rg16 => Pixels::Shorts(image.to_luma_alpha16().into_raw()),
+            TextureFormat::Rgba16 => Pixels::Shorts(image.to_rgba16().into_raw()),
+            TextureFormat::R16F | TextureFormat::R32F => Pixels::Floats(image.to_luma32f().into_raw()),
+            TextureFormat::Rg16F | TextureFormat::Rg32F => Pixels::Floats(image.to_luma_alpha32f().into_raw()),
+            TextureFormat::Rgba16F | TextureFormat::Rgba32F => Pixels::Floats(image.to_rgba32f().into_raw()),
+            _ => return Err(RendererError::Invalid(format!("Images can't be uploaded as {:?}", format))),
+        };
+        Ok(pixels)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const c_void {
+        match self {
+            Pixels::Bytes(bytes) => bytes.as_ptr() as *const _,
+            Pixels::Shorts(shorts) => shorts.as_ptr() as *const _,
+            Pixels::Floats(floats) => floats.as_ptr() as *const _,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayAlphaImage, GrayImage, ImageBuffer, Luma, LumaA, Rgb, Rgb32FImage, RgbImage, Rgba};
+
+    fn images() -> Vec<DynamicImage> {
+        vec![
+            DynamicImage::ImageLuma8(GrayImage::from_pixel(2, 1, Luma([64]))),
+            DynamicImage::ImageLumaA8(GrayAlphaImage::from_pixel(2, 1, LumaA([64, 128]))),
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([1, 2, 3]))),
+            DynamicImage::ImageLuma16(ImageBuffer::from_pixel(2, 1, Luma([1000u16]))),
+            DynamicImage::ImageLumaA16(ImageBuffer::from_pixel(2, 1, LumaA([1000u16, 65535]))),
+            DynamicImage::ImageRgba16(ImageBuffer::from_pixel(2, 1, Rgba([1u16, 2, 3, 4]))),
+            DynamicImage::ImageRgb32F(Rgb32FImage::from_pixel(2, 1, Rgb([4.0, 0.5, 0.0]))),
+        ]
+    }
+
+    #[test]
+    fn srgb_images_stay_colour() {
+        let formats: Vec<_> = images().iter().map(|image| TextureFormat::infer(image, ColorSpace::Srgb)).collect();
+        assert_eq!(formats, [
+            TextureFormat::Srgb8Alpha8,
+            TextureFormat::Srgb8Alpha8,
+            TextureFormat::Srgb8Alpha8,
+            TextureFormat::Srgb8Alpha8,
+            TextureFormat::Srgb8Alpha8,
+            TextureFormat::Srgb8Alpha8,
+            TextureFormat::Rgba32F,
+        ]);
+    }
+
+    #[test]
+    fn linear_images_keep_channels_and_precision() {
+        let formats: Vec<_> = images().iter().map(|image| TextureFormat::infer(image, ColorSpace::Linear)).collect();
+        assert_eq!(formats, [
+            TextureFormat::R8,
+            TextureFormat::Rg8,
+            TextureFormat::Rgba8,
+            TextureFormat::R16,
+            TextureFormat::Rg16,
+            TextureFormat::Rgba16,
+            TextureFormat::Rgba32F,
+        ]);
+    }
+
+    #[test]
+    fn convert_matches_the_format() {
+        let images = images();
+        let gray = &images[0];
+        match Pixels::convert(gray, TextureFormat::Srgb8Alpha8).unwrap() {
+            Pixels::Bytes(bytes) => assert_eq!(bytes, [64, 64, 64, 255, 64, 64, 64, 255]),
+            _ => panic!("expected bytes"),
+        }
+        match Pixels::convert(gray, TextureFormat::R8).unwrap() {
+            Pixels::Bytes(bytes) => assert_eq!(bytes, [64, 64]),
+            _ => panic!("expected bytes"),
+        }
+        match Pixels::convert(&images[4], TextureFormat::Rg16).unwrap() {
+            Pixels::Shorts(shorts) => assert_eq!(shorts, [1000, 65535, 1000, 65535]),
+            _ => panic!("expected shorts"),
+        }
+        match Pixels::convert(&images[5], TextureFormat::Rgba16).unwrap() {
+            Pixels::Shorts(shorts) => assert_eq!(shorts, [1, 2, 3, 4, 1, 2, 3, 4]),
+            _ => panic!("expected shorts"),
+        }
+        match Pixels::convert(&images[6], TextureFormat::Rgba32F).unwrap() {
+            Pixels::Floats(floats) => assert_eq!(floats, [4.0, 0.5, 0.0, 1.0, 4.0, 0.5, 0.0, 1.0]),
+            _ => panic!("expected floats"),
+        }
+    }
+
+    #[test]
+    fn depth_formats_cant_be_converted() {
+        let gray = &images()[0];
+        assert!(Pixels::convert(gray, TextureFormat::Depth24).is_err());
+        assert!(Pixels::convert(gray, TextureFormat::Depth24Stencil8).is_err());
+    }
+}
@@ -1,8 +1,163 @@
-use glm::{Vec2, Vec3, Vec4};
+use glm::{UVec4, Vec2, Vec3, Vec4};
+use num_traits::Zero;
+use crate::renderer::vertex_layout::{vertex_layout, Normalized};
 
-#[repr(C)]
-pub struct Vertex {
-    pub v: Vec3,
-    pub vn: Vec3,
-    pub vt: Vec2,
+vertex_layout! {
+    /// The default vertex.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Vertex {
+        #[location(0)] pub v: Vec3,
+        #[location(1)] pub vn: Vec3,
+        #[location(2)] pub vt: Vec2,
+    }
+}
+
+vertex_layout! {
+    /// The default vertex plus a tangent for normal mapping, see
+    /// `MeshData::compute_tangents`. `tangent.xyz` is the tangent, `tangent.w`
+    /// the handedness of the bitangent.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct TangentVertex {
+        #[location(0)] pub v: Vec3,
+        #[location(1)] pub vn: Vec3,
+        #[location(2)] pub vt: Vec2,
+        #[location(3)] pub tangent: Vec4,
+    }
+}
+
+vertex_layout! {
+    /// Position only, for depth and shadow passes.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct PositionVertex {
+        #[location(0)] pub v: Vec3,
+    }
+}
+
+vertex_layout! {
+    /// Position with an 8 bit colour, for debug geometry.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct ColorVertex {
+        #[location(0)] pub v: Vec3,
+        #[location(4)] pub color: Normalized<[u8; 4]>,
+    }
+}
+
+vertex_layout! {
+    /// The default vertex with a second uv channel, e.g. for light maps.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct LightmapVertex {
+        #[location(0)] pub v: Vec3,
+        #[location(1)] pub vn: Vec3,
+        #[location(2)] pub vt: Vec2,
+        #[location(7)] pub vt2: Vec2,
+    }
+}
+
+vertex_layout! {
+    /// The default vertex plus up to four joints and their weights.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct SkinnedVertex {
+        #[location(0)] pub v: Vec3,
+        #[location(1)] pub vn: Vec3,
+        #[location(2)] pub vt: Vec2,
+        #[location(5)] pub joints: UVec4,
+        #[location(6)] pub weights: Vec4,
+    }
+}
+
+impl Vertex {
+    pub fn new(v: Vec3, vn: Vec3, vt: Vec2) -> Self {
+        Self { v, vn, vt }
+    }
+}
+
+impl TangentVertex {
+    pub fn new(vertex: Vertex, tangent: Vec4) -> Self {
+        Self { v: vertex.v, vn: vertex.vn, vt: vertex.vt, tangent }
+    }
+
+    pub fn bitangent(&self) -> Vec3 {
+        glm::cross(self.vn, self.tangent.truncate(3)) * self.tangent.w
+    }
+}
+
+impl From<Vertex> for PositionVertex {
+    fn from(vertex: Vertex) -> Self {
+        Self { v: vertex.v }
+    }
+}
+
+impl From<Vertex> for ColorVertex {
+    fn from(vertex: Vertex) -> Self {
+        Self { v: vertex.v, color: Normalized([255; 4]) }
+    }
+}
+
+impl From<Vertex> for LightmapVertex {
+    fn from(vertex: Vertex) -> Self {
+        Self { v: vertex.v, vn: vertex.vn, vt: vertex.vt, vt2: vertex.vt }
+    }
+}
+
+/// Binds every vertex fully to joint 0.
+impl From<Vertex> for SkinnedVertex {
+    fn from(vertex: Vertex) -> Self {
+        Self {
+            v: vertex.v,
+            vn: vertex.vn,
+            vt: vertex.vt,
+            joints: UVec4::zero(),
+            weights: Vec4::new(1.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::vertex_layout::{AttributeKind, ComponentType, VertexLayout};
+    use super::*;
+
+    /// `(location, components, offset)` of every attribute.
+    fn summary<V: VertexLayout>() -> Vec<(u32, i32, usize)> {
+        V::ATTRIBUTES.iter().map(|a| (a.location, a.components, a.offset)).collect()
+    }
+
+    #[test]
+    fn layouts_match_the_structs() {
+        assert_eq!(summary::<Vertex>(), [(0, 3, 0), (1, 3, 12), (2, 2, 24)]);
+        assert_eq!(size_of::<Vertex>(), 32);
+        assert_eq!(summary::<TangentVertex>(), [(0, 3, 0), (1, 3, 12), (2, 2, 24), (3, 4, 32)]);
+        assert_eq!(size_of::<TangentVertex>(), 48);
+        assert_eq!(summary::<PositionVertex>(), [(0, 3, 0)]);
+        assert_eq!(summary::<ColorVertex>(), [(0, 3, 0), (4, 4, 12)]);
+        assert_eq!(size_of::<ColorVertex>(), 16);
+        assert_eq!(summary::<LightmapVertex>(), [(0, 3, 0), (1, 3, 12), (2, 2, 24), (7, 2, 32)]);
+        assert_eq!(summary::<SkinnedVertex>(), [(0, 3, 0), (1, 3, 12), (2, 2, 24), (5, 4, 32), (6, 4, 48)]);
+    }
+
+    #[test]
+    fn attribute_kinds() {
+        let color = ColorVertex::ATTRIBUTES[1];
+        assert_eq!((color.component_type, color.kind), (ComponentType::U8, AttributeKind::Normalized));
+        let joints = SkinnedVertex::ATTRIBUTES[3];
+        assert_eq!((joints.component_type, joints.kind), (ComponentType::U32, AttributeKind::Integer));
+        let weights = SkinnedVertex::ATTRIBUTES[4];
+        assert_eq!((weights.component_type, weights.kind), (ComponentType::F32, AttributeKind::Float));
+    }
+
+    #[test]
+    fn conversions_keep_shared_attributes() {
+        let vertex = Vertex::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0), Vec2::new(0.25, 0.75));
+
+        assert_eq!(PositionVertex::from(vertex).v, vertex.v);
+        assert_eq!(ColorVertex::from(vertex).color, Normalized([255; 4]));
+
+        let lightmap = LightmapVertex::from(vertex);
+        assert_eq!((lightmap.v, lightmap.vn, lightmap.vt, lightmap.vt2), (vertex.v, vertex.vn, vertex.vt, vertex.vt));
+
+        let skinned = SkinnedVertex::from(vertex);
+        assert_eq!((skinned.v, skinned.vn, skinned.vt), (vertex.v, vertex.vn, vertex.vt));
+        assert_eq!(skinned.joints, UVec4::zero());
+        assert_eq!(skinned.weights, Vec4::new(1.0, 0.0, 0.0, 0.0));
+    }
 }
@@ -0,0 +1,184 @@
+use glm::{IVec2, IVec3, IVec4, Mat3, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+
+/// Type of a single component as stored in the vertex buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+}
+
+impl ComponentType {
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            ComponentType::I8 => gl::BYTE,
+            ComponentType::U8 => gl::UNSIGNED_BYTE,
+            ComponentType::I16 => gl::SHORT,
+            ComponentType::U16 => gl::UNSIGNED_SHORT,
+            ComponentType::I32 => gl::INT,
+            ComponentType::U32 => gl::UNSIGNED_INT,
+            ComponentType::F32 => gl::FLOAT,
+        }
+    }
+
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::I16 | ComponentType::U16 => 2,
+            ComponentType::I32 | ComponentType::U32 | ComponentType::F32 => 4,
+        }
+    }
+}
+
+/// How the shader sees an attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeKind {
+    /// `float`/`vecN`, components are converted as is.
+    Float,
+    /// `float`/`vecN`, integer components are mapped to 0..1 (unsigned) or -1..1 (signed).
+    Normalized,
+    /// `int`/`ivecN`/`uint`/`uvecN`, components stay integers.
+    Integer,
+}
+
+/// One attribute of a vertex type, the Rust side of a `layout (location = N) in ...`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    /// Consecutive locations taken, e.g. 4 for a `mat4` with one column per location.
+    pub columns: u32,
+    pub components: i32,
+    pub component_type: ComponentType,
+    pub kind: AttributeKind,
+    /// Byte offset inside the vertex.
+    pub offset: usize,
+}
+
+/// Describes how a `#[repr(C)]` vertex type is laid out in a vertex buffer.
+/// Usually implemented through `vertex_layout!`.
+pub trait VertexLayout: Copy {
+    const ATTRIBUTES: &'static [VertexAttribute];
+}
+
+impl VertexAttribute {
+    /// Bytes of one column.
+    pub fn column_size(&self) -> usize {
+        self.components as usize * self.component_type.size()
+    }
+}
+
+/// Rust types that can be used as vertex attributes.
+pub trait AttributeFormat {
+    const COLUMNS: u32 = 1;
+    const COMPONENTS: i32;
+    const COMPONENT_TYPE: ComponentType;
+    const KIND: AttributeKind;
+}
+
+impl AttributeFormat for Mat3 {
+    const COLUMNS: u32 = 3;
+    const COMPONENTS: i32 = 3;
+    const COMPONENT_TYPE: ComponentType = ComponentType::F32;
+    const KIND: AttributeKind = AttributeKind::Float;
+}
+
+impl AttributeFormat for Mat4 {
+    const COLUMNS: u32 = 4;
+    const COMPONENTS: i32 = 4;
+    const COMPONENT_TYPE: ComponentType = ComponentType::F32;
+    const KIND: AttributeKind = AttributeKind::Float;
+}
+
+/// Marks integer data that should reach the shader as normalized floats,
+/// e.g. `Normalized<[u8; 4]>` for 8 bit colours.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normalized<T>(pub T);
+
+macro_rules! impl_attribute_format {
+    ($($t:ty => $components:literal, $component_type:ident, $kind:ident;)*) => {
+        $(
+            impl AttributeFormat for $t {
+                const COMPONENTS: i32 = $components;
+                const COMPONENT_TYPE: ComponentType = ComponentType::$component_type;
+                const KIND: AttributeKind = AttributeKind::$kind;
+            }
+        )*
+    };
+}
+
+impl_attribute_format! {
+    f32 => 1, F32, Float;
+    Vec2 => 2, F32, Float;
+    Vec3 => 3, F32, Float;
+    Vec4 => 4, F32, Float;
+    [f32; 2] => 2, F32, Float;
+    [f32; 3] => 3, F32, Float;
+    [f32; 4] => 4, F32, Float;
+
+    i32 => 1, I32, Integer;
+    IVec2 => 2, I32, Integer;
+    IVec3 => 3, I32, Integer;
+    IVec4 => 4, I32, Integer;
+    u32 => 1, U32, Integer;
+    UVec2 => 2, U32, Integer;
+    UVec3 => 3, U32, Integer;
+    UVec4 => 4, U32, Integer;
+    [u8; 4] => 4, U8, Integer;
+    [u16; 4] => 4, U16, Integer;
+
+    Normalized<[u8; 4]> => 4, U8, Normalized;
+    Normalized<[i8; 4]> => 4, I8, Normalized;
+    Normalized<[u16; 2]> => 2, U16, Normalized;
+    Normalized<[u16; 4]> => 4, U16, Normalized;
+    Normalized<[i16; 4]> => 4, I16, Normalized;
+}
+
+/// Declares a `#[repr(C)]` vertex struct and implements `VertexLayout` for it.
+/// Every field names the shader location it is bound to:
+///
+/// ```ignore
+/// vertex_layout! {
+///     #[derive(Clone, Copy)]
+///     pub struct ColorVertex {
+///         #[location(0)] pub v: Vec3,
+///         #[location(1)] pub color: Normalized<[u8; 4]>,
+///     }
+/// }
+/// ```
+macro_rules! vertex_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(#[location($location:literal)] $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $crate::renderer::vertex_layout::VertexLayout for $name {
+            const ATTRIBUTES: &'static [$crate::renderer::vertex_layout::VertexAttribute] = &[
+                $(
+                    $crate::renderer::vertex_layout::VertexAttribute {
+                        location: $location,
+                        columns: <$ty as $crate::renderer::vertex_layout::AttributeFormat>::COLUMNS,
+                        components: <$ty as $crate::renderer::vertex_layout::AttributeFormat>::COMPONENTS,
+                        component_type: <$ty as $crate::renderer::vertex_layout::AttributeFormat>::COMPONENT_TYPE,
+                        kind: <$ty as $crate::renderer::vertex_layout::AttributeFormat>::KIND,
+                        offset: std::mem::offset_of!($name, $field),
+                    }
+                ),*
+            ];
+        }
+    };
+}
+
+pub(crate) use vertex_layout;